@@ -0,0 +1,98 @@
+//! 去背景, 可以在配置里切换不同的背景建模算法
+use opencv::bgsegm::{
+    create_background_subtractor_cnt, create_background_subtractor_gmg,
+    create_background_subtractor_mog, BackgroundSubtractorCNT, BackgroundSubtractorGMG,
+    BackgroundSubtractorMOG,
+};
+use opencv::core::Ptr;
+use opencv::prelude::*;
+use opencv::video::{
+    create_background_subtractor_knn, create_background_subtractor_mog2, BackgroundSubtractorKNN,
+    BackgroundSubtractorMOG2,
+};
+
+use crate::config::{BackgroundConfig, BackgroundKind};
+
+/// 背景模型, 输入灰度帧, 输出前景掩码
+pub trait BackgroundModel {
+    /// 前景是255, 背景是0; 开了阴影检测的 MOG2 / KNN 会把阴影标成127
+    ///
+    /// `learning_rate` 为负数时由算法自己决定
+    fn segment(&mut self, frame: &Mat, mask: &mut Mat, learning_rate: f64) -> opencv::Result<()>;
+}
+
+macro_rules! impl_background_model {
+    ($($subtractor:ty),*) => {
+        $(
+            impl BackgroundModel for Ptr<$subtractor> {
+                fn segment(
+                    &mut self,
+                    frame: &Mat,
+                    mask: &mut Mat,
+                    learning_rate: f64,
+                ) -> opencv::Result<()> {
+                    self.apply(frame, mask, learning_rate)
+                }
+            }
+        )*
+    };
+}
+
+impl_background_model!(
+    BackgroundSubtractorMOG,
+    BackgroundSubtractorMOG2,
+    BackgroundSubtractorKNN,
+    BackgroundSubtractorGMG,
+    BackgroundSubtractorCNT
+);
+
+/// 按配置里的 `model` 创建背景模型
+pub fn create_background_model(
+    config: &BackgroundConfig,
+) -> opencv::Result<Box<dyn BackgroundModel>> {
+    let model: Box<dyn BackgroundModel> = match config.model {
+        BackgroundKind::Mog => {
+            let mog = &config.mog;
+            Box::new(create_background_subtractor_mog(
+                mog.history,
+                mog.mixtures,
+                mog.background_ratio,
+                mog.noise_sigma,
+            )?)
+        }
+        BackgroundKind::Mog2 => {
+            let mog2 = &config.mog2;
+            Box::new(create_background_subtractor_mog2(
+                mog2.history,
+                mog2.var_threshold,
+                mog2.detect_shadows,
+            )?)
+        }
+        BackgroundKind::Knn => {
+            let knn = &config.knn;
+            Box::new(create_background_subtractor_knn(
+                knn.history,
+                knn.dist2_threshold,
+                knn.detect_shadows,
+            )?)
+        }
+        BackgroundKind::Gmg => {
+            let gmg = &config.gmg;
+            Box::new(create_background_subtractor_gmg(
+                gmg.initialization_frames,
+                gmg.decision_threshold,
+            )?)
+        }
+        BackgroundKind::Cnt => {
+            let cnt = &config.cnt;
+            Box::new(create_background_subtractor_cnt(
+                cnt.min_pixel_stability,
+                cnt.use_history,
+                cnt.max_pixel_stability,
+                cnt.is_parallel,
+            )?)
+        }
+    };
+
+    Ok(model)
+}
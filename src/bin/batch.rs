@@ -0,0 +1,171 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use clap::Parser;
+use cv_demo::export::record_sink;
+use cv_demo::output::VIDEO_EXTENSIONS;
+use cv_demo::parallel;
+use cv_demo::source::{open_source, Frame};
+use cv_demo::summary::{BatchSummary, VideoSummary};
+use cv_demo::{FrameRecord, PipelineConfig, VehicleCounter};
+
+#[derive(Parser)]
+#[command(about = "批量车辆计数: 并行处理目录里的所有视频, 输出每个视频的结果和汇总")]
+struct Cli {
+    /// 视频所在目录
+    #[arg(short, long)]
+    dir: PathBuf,
+
+    /// 结果目录: 每个视频一个逐帧结果文件(比如 cam1.mp4.jsonl), 外加 summary.csv 和 summary.json
+    #[arg(short, long)]
+    out: PathBuf,
+
+    /// 配置文件路径(.toml / .json), 不传就用默认配置
+    #[arg(short, long)]
+    config: Option<String>,
+
+    /// 同时处理几个视频, 默认等于 CPU 核数
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    /// 每隔几帧处理一帧, 1表示每帧都处理
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    every: u64,
+
+    /// 逐帧结果写成 CSV, 默认写 JSON Lines
+    #[arg(long)]
+    csv: bool,
+}
+
+/// 目录里所有的视频, 按文件名排序
+fn list_videos(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut videos: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+        })
+        .collect();
+    videos.sort();
+
+    Ok(videos)
+}
+
+/// 跑完一个视频, 逐帧结果写到 `records`
+fn count_video(
+    video: &Path,
+    records: &Path,
+    config: &PipelineConfig,
+    every: u64,
+) -> Result<VideoSummary, Box<dyn Error>> {
+    let started = Instant::now();
+
+    let mut source = open_source(&video.to_string_lossy(), None)?;
+    let mut counter = VehicleCounter::from_config(config)?;
+    counter.set_fps(source.fps());
+
+    let mut sink = record_sink(records)?;
+
+    let mut summary = VideoSummary {
+        video: video.to_string_lossy().into_owned(),
+        ..VideoSummary::default()
+    };
+
+    while let Some(Frame { index, time, image }) = source.read()? {
+        let result = counter.process_frame_at(&image, time)?;
+        sink.write(&FrameRecord::new(index, time * 1000f64, &result))?;
+
+        summary.frames = index + 1;
+        summary.processed += 1;
+        summary.processed_until = time;
+
+        for _ in 1..every {
+            if !source.skip()? {
+                break;
+            }
+            summary.frames += 1;
+        }
+    }
+    sink.finish()?;
+
+    summary.counts = counter.counts();
+    summary.classes = counter.classes();
+    summary.elapsed = started.elapsed().as_secs_f64();
+    if summary.elapsed > 0f64 {
+        summary.processing_fps = summary.processed as f64 / summary.elapsed;
+    }
+
+    Ok(summary)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+
+    let config = match &cli.config {
+        Some(path) => PipelineConfig::load(path)?,
+        None => PipelineConfig::default(),
+    };
+
+    let videos = list_videos(&cli.dir)?;
+    if videos.is_empty() {
+        return Err(format!("no videos found in {}", cli.dir.display()).into());
+    }
+    fs::create_dir_all(&cli.out)?;
+
+    let extension = if cli.csv { "csv" } else { "jsonl" };
+
+    let started = Instant::now();
+
+    // 结果按视频原来的顺序放好
+    let summaries = parallel::map(
+        &videos,
+        cli.jobs,
+        |video| {
+            // 带上视频的扩展名, cam1.mp4 和 cam1.avi 不会写到同一个文件
+            let name = video.file_name().unwrap_or_default().to_string_lossy();
+            let records = cli.out.join(format!("{}.{}", name, extension));
+
+            count_video(video, &records, &config, cli.every).unwrap_or_else(|err| VideoSummary {
+                video: video.to_string_lossy().into_owned(),
+                error: Some(err.to_string()),
+                ..VideoSummary::default()
+            })
+        },
+        |finished, video, summary| match &summary.error {
+            Some(err) => eprintln!(
+                "[{}/{}] {}: failed: {}",
+                finished,
+                videos.len(),
+                video.display(),
+                err
+            ),
+            None => eprintln!(
+                "[{}/{}] {}: {} vehicles, {:.1} fps",
+                finished,
+                videos.len(),
+                video.display(),
+                summary.counts.total(),
+                summary.processing_fps
+            ),
+        },
+    );
+
+    let summary = BatchSummary::new(summaries, started.elapsed().as_secs_f64());
+    summary.write_csv(cli.out.join("summary.csv"))?;
+    summary.write_json(cli.out.join("summary.json"))?;
+
+    eprintln!(
+        "{} videos, {} failed, {} vehicles (down {}, up {}), {:.1} fps",
+        summary.videos.len(),
+        summary.failed,
+        summary.counts.total(),
+        summary.counts.down,
+        summary.counts.up,
+        summary.processing_fps
+    );
+
+    Ok(())
+}
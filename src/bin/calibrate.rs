@@ -0,0 +1,395 @@
+use std::error::Error;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use clap::{Args, Parser, Subcommand};
+use cv_demo::cli::OutputArgs;
+use cv_demo::config::{CalibrationConfig, LaneConfig, LineConfig, RoiConfig};
+use cv_demo::pipeline::draw_geometry;
+use cv_demo::source::open_source;
+use cv_demo::PipelineConfig;
+use opencv::core::{Point, Scalar};
+use opencv::highgui::{
+    destroy_all_windows, imshow, named_window_def, set_mouse_callback, wait_key, EVENT_LBUTTONDOWN,
+    EVENT_RBUTTONDOWN,
+};
+use opencv::imgproc::{circle_def, polylines_def, put_text_def, FONT_HERSHEY_SIMPLEX};
+use opencv::prelude::*;
+use opencv::types::VectorOfPoint;
+
+const WINDOW: &str = "calibrate";
+
+#[derive(Parser)]
+#[command(about = "标定检测线, 车道, 感兴趣区域和测速标定点")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// 从哪一帧上标定
+#[derive(Debug, Clone, Args)]
+struct FrameArgs {
+    /// 输入: 视频文件, 图片目录或者通配符(frames/*.png), 摄像头编号, 视频流地址(rtsp://...)
+    #[arg(short, long)]
+    input: String,
+
+    /// 用第几帧(从0开始)
+    #[arg(long, default_value_t = 0)]
+    frame: u64,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 在画面上点击标定, 保存到配置文件(文件里的注释会丢掉)
+    Edit {
+        #[command(flatten)]
+        frame: FrameArgs,
+
+        /// 配置文件路径(.toml / .json), 不存在就从默认配置开始
+        #[arg(short, long)]
+        config: String,
+    },
+    /// 把配置里的检测线, 区域, 车道和标定点画到画面上检查
+    Render {
+        #[command(flatten)]
+        frame: FrameArgs,
+
+        /// 配置文件路径(.toml / .json)
+        #[arg(short, long)]
+        config: String,
+
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+}
+
+fn read_frame(args: &FrameArgs) -> Result<Mat, Box<dyn Error>> {
+    let mut source = open_source(&args.input, None)?;
+    for _ in 0..args.frame {
+        if !source.skip()? {
+            break;
+        }
+    }
+
+    match source.read()? {
+        Some(frame) => Ok(frame.image),
+        None => Err(format!("{} has no frame {}", args.input, args.frame).into()),
+    }
+}
+
+/// 正在画的是什么
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tool {
+    /// 检测线, 至少两个点
+    Line,
+    /// 车道, 至少三个点
+    Lane,
+    /// 感兴趣区域, 至少三个点
+    Roi,
+    /// 测速标定点, 正好四个点
+    Perspective,
+}
+
+impl Tool {
+    fn name(&self) -> &'static str {
+        match self {
+            Tool::Line => "line",
+            Tool::Lane => "lane",
+            Tool::Roi => "roi",
+            Tool::Perspective => "perspective",
+        }
+    }
+}
+
+struct Editor {
+    config: PipelineConfig,
+    tool: Tool,
+    /// 还没画完的点
+    points: Vec<Point>,
+    saved: bool,
+}
+
+impl Editor {
+    fn new(config: PipelineConfig) -> Self {
+        Self {
+            config,
+            tool: Tool::Line,
+            points: Vec::new(),
+            saved: true,
+        }
+    }
+
+    fn switch(&mut self, tool: Tool) {
+        if !self.points.is_empty() {
+            eprintln!("discarded {} unfinished points", self.points.len());
+            self.points.clear();
+        }
+        self.tool = tool;
+        eprintln!("tool: {}", tool.name());
+    }
+
+    /// 没被用过的名字, 比如 `line2`
+    fn unused_name(&self, prefix: &str) -> String {
+        let names: Vec<&str> = self
+            .config
+            .lines
+            .iter()
+            .map(|line| line.name.as_str())
+            .chain(self.config.zones.iter().map(|zone| zone.name.as_str()))
+            .chain(self.config.lanes.iter().map(|lane| lane.name.as_str()))
+            .collect();
+
+        (1..)
+            .map(|n| format!("{}{}", prefix, n))
+            .find(|name| !names.contains(&name.as_str()))
+            .unwrap()
+    }
+
+    /// 把正在画的点存成一条线 / 一个区域 / 一组标定点
+    fn finish(&mut self) -> io::Result<()> {
+        let points: Vec<[i32; 2]> = self.points.iter().map(|p| [p.x, p.y]).collect();
+
+        match self.tool {
+            Tool::Line => {
+                if points.len() < 2 {
+                    eprintln!("a line needs at least 2 points");
+                    return Ok(());
+                }
+                let name = self.unused_name("line");
+                eprintln!("added {}", name);
+                self.config.lines.push(LineConfig {
+                    name,
+                    points,
+                    allowed: None,
+                });
+            }
+            Tool::Lane => {
+                if points.len() < 3 {
+                    eprintln!("a lane needs at least 3 points");
+                    return Ok(());
+                }
+                let name = self.unused_name("lane");
+                eprintln!("added {}", name);
+                self.config.lanes.push(LaneConfig {
+                    name,
+                    polygon: points,
+                });
+            }
+            Tool::Roi => {
+                if points.len() < 3 {
+                    eprintln!("a roi polygon needs at least 3 points");
+                    return Ok(());
+                }
+                let roi = self.config.roi.get_or_insert(RoiConfig {
+                    polygons: Vec::new(),
+                    mask: None,
+                    crop: false,
+                });
+                roi.polygons.push(points);
+                eprintln!("added roi polygon {}", roi.polygons.len());
+            }
+            Tool::Perspective => {
+                if points.len() != 4 {
+                    eprintln!("perspective calibration needs exactly 4 points");
+                    return Ok(());
+                }
+                let previous = self.config.calibration.clone();
+                let world = read_world_points(previous.as_ref().map(|c| c.world.as_slice()))?;
+                let image = points.iter().map(|[x, y]| [*x as f64, *y as f64]).collect();
+                self.config.calibration = Some(match previous {
+                    Some(calibration) => CalibrationConfig {
+                        image,
+                        world,
+                        ..calibration
+                    },
+                    None => CalibrationConfig {
+                        image,
+                        world,
+                        speed_limit: None,
+                        window: 1f64,
+                    },
+                });
+                eprintln!("updated calibration");
+            }
+        }
+
+        self.points.clear();
+        self.saved = false;
+
+        Ok(())
+    }
+
+    /// 清掉当前工具已经存下的所有图形
+    fn clear(&mut self) {
+        match self.tool {
+            Tool::Line => self.config.lines.clear(),
+            Tool::Lane => self.config.lanes.clear(),
+            Tool::Roi => self.config.roi = None,
+            Tool::Perspective => self.config.calibration = None,
+        }
+        self.points.clear();
+        self.saved = false;
+        eprintln!("cleared all {} shapes", self.tool.name());
+    }
+
+    fn draw(&self, frame: &Mat) -> opencv::Result<Mat> {
+        let mut canvas = frame.try_clone()?;
+        draw_geometry(&mut canvas, &self.config)?;
+
+        let color = Scalar::new(255f64, 0f64, 255f64, 0f64);
+        let points = VectorOfPoint::from_iter(self.points.iter().copied());
+        polylines_def(&mut canvas, &points, self.tool != Tool::Line, color)?;
+        for point in &self.points {
+            circle_def(&mut canvas, *point, 4, color)?;
+        }
+
+        let status = format!(
+            "[{}{}] l/a/r/p: tool  click: add  right click/u: undo  enter: finish  c: clear  s: save  q: quit",
+            self.tool.name(),
+            if self.saved { "" } else { " *" }
+        );
+        put_text_def(
+            &mut canvas,
+            &status,
+            Point::new(10, 20),
+            FONT_HERSHEY_SIMPLEX,
+            0.5f64,
+            color,
+        )?;
+
+        Ok(canvas)
+    }
+}
+
+/// 在终端里输入四个标定点在路面上的坐标(米), 直接回车沿用原来的值
+fn read_world_points(previous: Option<&[[f64; 2]]>) -> io::Result<Vec<[f64; 2]>> {
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut world = Vec::new();
+
+    while world.len() < 4 {
+        let index = world.len();
+        let default = previous.and_then(|points| points.get(index)).copied();
+        match default {
+            Some([x, y]) => print!(
+                "world point {} in meters \"x y\" [{} {}]: ",
+                index + 1,
+                x,
+                y
+            ),
+            None => print!("world point {} in meters \"x y\": ", index + 1),
+        }
+        io::stdout().flush()?;
+
+        let Some(line) = lines.next().transpose()? else {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "world points are required",
+            ));
+        };
+
+        let values: Vec<f64> = line
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|value| !value.is_empty())
+            .filter_map(|value| value.parse().ok())
+            .collect();
+        match (values.as_slice(), default) {
+            ([x, y], _) => world.push([*x, *y]),
+            ([], Some(point)) => world.push(point),
+            _ => eprintln!("expected two numbers"),
+        }
+    }
+
+    Ok(world)
+}
+
+fn edit(args: &FrameArgs, path: &str) -> Result<(), Box<dyn Error>> {
+    let config = if Path::new(path).exists() {
+        PipelineConfig::load(path)?
+    } else {
+        eprintln!("{} does not exist, starting from the default config", path);
+        PipelineConfig::default()
+    };
+    let frame = read_frame(args)?;
+    let mut editor = Editor::new(config);
+
+    // 回调在 highgui 的线程里, 点击先放进队列, 主循环里再处理
+    let clicks = Arc::new(Mutex::new(Vec::new()));
+    named_window_def(WINDOW)?;
+    let queue = Arc::clone(&clicks);
+    set_mouse_callback(
+        WINDOW,
+        Some(Box::new(move |event, x, y, _flags| {
+            if event == EVENT_LBUTTONDOWN || event == EVENT_RBUTTONDOWN {
+                queue.lock().unwrap().push((event, Point::new(x, y)));
+            }
+        })),
+    )?;
+
+    loop {
+        for (event, point) in clicks.lock().unwrap().drain(..) {
+            if event == EVENT_LBUTTONDOWN {
+                editor.points.push(point);
+            } else {
+                editor.points.pop();
+            }
+        }
+
+        imshow(WINDOW, &editor.draw(&frame)?)?;
+
+        match wait_key(30)? {
+            key if key == 'l' as i32 => editor.switch(Tool::Line),
+            key if key == 'a' as i32 => editor.switch(Tool::Lane),
+            key if key == 'r' as i32 => editor.switch(Tool::Roi),
+            key if key == 'p' as i32 => editor.switch(Tool::Perspective),
+            key if key == 'u' as i32 => {
+                editor.points.pop();
+            }
+            key if key == 'c' as i32 => editor.clear(),
+            // 回车
+            10 | 13 => editor.finish()?,
+            key if key == 's' as i32 => match editor.config.validate() {
+                Ok(()) => {
+                    editor.config.save(path)?;
+                    editor.saved = true;
+                    eprintln!("saved to {}", path);
+                }
+                Err(err) => eprintln!("not saved: {}", err),
+            },
+            // q 或者 Esc
+            key if key == 'q' as i32 || key == 27 => {
+                if !editor.saved {
+                    eprintln!("quit without saving");
+                }
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    destroy_all_windows()?;
+
+    Ok(())
+}
+
+fn render(args: &FrameArgs, path: &str, output: &OutputArgs) -> Result<(), Box<dyn Error>> {
+    let config = PipelineConfig::load(path)?;
+    let mut frame = read_frame(args)?;
+
+    draw_geometry(&mut frame, &config)?;
+    output.show(WINDOW, &frame)?;
+
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    match Cli::parse().command {
+        Command::Edit { frame, config } => edit(&frame, &config),
+        Command::Render {
+            frame,
+            config,
+            output,
+        } => render(&frame, &config, &output),
+    }
+}
@@ -1,8 +1,8 @@
+use clap::{Parser, Subcommand};
+use cv_demo::cli::ImageArgs;
 use opencv::core::{
-    add, no_array, Point, Point2f, Rect, Size, Size_, BORDER_CONSTANT, BORDER_DEFAULT, CV_32FC1,
-    CV_64F, CV_64FC1, CV_64FC3, CV_64FC4, CV_8UC1, DECOMP_LU,
+    add, no_array, Point, Point2f, Size, Size_, BORDER_CONSTANT, BORDER_DEFAULT, DECOMP_LU,
 };
-use opencv::highgui::{imshow, wait_key};
 use opencv::imgcodecs::{imread, IMREAD_COLOR};
 use opencv::imgproc::{
     bilateral_filter, blur, canny, filter_2d, gaussian_blur, get_perspective_transform,
@@ -12,8 +12,8 @@ use opencv::imgproc::{
 use opencv::prelude::*;
 
 /// 放大缩小图片
-fn resize_func() -> opencv::Result<()> {
-    let mat = imread("C:\\Users\\Administrator\\Desktop\\1.png", IMREAD_COLOR)?;
+fn resize_func(args: &ImageArgs) -> opencv::Result<()> {
+    let mat = imread(&args.input, IMREAD_COLOR)?;
 
     let mut new = Mat::default();
 
@@ -40,8 +40,7 @@ fn resize_func() -> opencv::Result<()> {
         0.9f64,
         INTER_AREA,
     )?;
-    imshow("asdsa", &new)?;
-    wait_key(10000)?;
+    args.show("asdsa", &new)?;
 
     Ok(())
 }
@@ -51,8 +50,8 @@ fn resize_func() -> opencv::Result<()> {
 /// 图像旋转, 平移或放大缩小的过程叫仿射变换
 ///
 /// 平移需要2*3的矩阵
-fn fangshebianhuan() -> opencv::Result<()> {
-    let mat = imread("C:\\Users\\Administrator\\Desktop\\dog.jpeg", IMREAD_COLOR)?;
+fn fangshebianhuan(args: &ImageArgs) -> opencv::Result<()> {
+    let mat = imread(&args.input, IMREAD_COLOR)?;
 
     // 通过切片创造Mat
     // [1, 0, 100]    100是通过x轴向右偏移100个像素
@@ -77,9 +76,7 @@ fn fangshebianhuan() -> opencv::Result<()> {
         0.into(),
     )?;
 
-    imshow("adasdas", &new_mat)?;
-
-    wait_key(10000)?;
+    args.show("adasdas", &new_mat)?;
 
     Ok(())
 }
@@ -87,8 +84,8 @@ fn fangshebianhuan() -> opencv::Result<()> {
 /// 透视变换
 ///
 /// 将一个坐标系变成另外的坐标系(小学课本拍张铺平)
-fn toushibianhuan() -> opencv::Result<()> {
-    let mat = imread("C:\\Users\\Administrator\\Desktop\\2.jpeg", IMREAD_COLOR)?;
+fn toushibianhuan(args: &ImageArgs) -> opencv::Result<()> {
+    let mat = imread(&args.input, IMREAD_COLOR)?;
 
     // 通过切片创造Mat
     // 四个数组分别为图中的四个角
@@ -124,9 +121,7 @@ fn toushibianhuan() -> opencv::Result<()> {
         0.into(),
     )?;
 
-    imshow("adasdas", &new_mat)?;
-
-    wait_key(10000)?;
+    args.show("adasdas", &new_mat)?;
 
     Ok(())
 }
@@ -134,8 +129,8 @@ fn toushibianhuan() -> opencv::Result<()> {
 /// 低通滤波
 ///
 /// 降噪和平滑图像
-fn juanji() -> opencv::Result<()> {
-    let mat = imread("C:\\Users\\Administrator\\Desktop\\1.png", IMREAD_COLOR)?;
+fn juanji(args: &ImageArgs) -> opencv::Result<()> {
+    let mat = imread(&args.input, IMREAD_COLOR)?;
 
     let calc = 1.0 / 25.0;
 
@@ -160,18 +155,16 @@ fn juanji() -> opencv::Result<()> {
         BORDER_DEFAULT,
     )?;
 
-    imshow("adssa", &new_mat)?;
-
-    wait_key(10000)?;
+    args.show("adssa", &new_mat)?;
 
     Ok(())
 }
 
 /// 低通 - 均值滤波
-fn junzhilvbo() -> opencv::Result<()> {
+fn junzhilvbo(args: &ImageArgs) -> opencv::Result<()> {
     let mut new_mat = Mat::default();
 
-    let mat = imread("C:\\Users\\Administrator\\Desktop\\1.png", IMREAD_COLOR)?;
+    let mat = imread(&args.input, IMREAD_COLOR)?;
 
     blur(
         &mat,
@@ -181,18 +174,16 @@ fn junzhilvbo() -> opencv::Result<()> {
         BORDER_DEFAULT,
     )?;
 
-    imshow("asdsa", &new_mat)?;
-
-    wait_key(10000)?;
+    args.show("asdsa", &new_mat)?;
 
     Ok(())
 }
 
 /// 低通 - 高斯滤波(解决高斯噪音, 小噪音, 不是胡椒那么大的)
-fn gaosilvbo() -> opencv::Result<()> {
+fn gaosilvbo(args: &ImageArgs) -> opencv::Result<()> {
     let mut new_mat = Mat::default();
 
-    let mat = imread("C:\\Users\\Administrator\\Desktop\\3.png", IMREAD_COLOR)?;
+    let mat = imread(&args.input, IMREAD_COLOR)?;
 
     gaussian_blur(
         &mat,
@@ -203,12 +194,9 @@ fn gaosilvbo() -> opencv::Result<()> {
         BORDER_DEFAULT,
     )?;
 
-    imshow("asdsa", &mat)?;
-    wait_key(10000)?;
-
-    imshow("asdsa", &new_mat)?;
+    args.preview("asdsa", &mat)?;
 
-    wait_key(10000)?;
+    args.show("asdsa", &new_mat)?;
 
     Ok(())
 }
@@ -216,35 +204,29 @@ fn gaosilvbo() -> opencv::Result<()> {
 /// 低通 - 中值滤波(对胡椒噪音效果明显)
 ///
 ///取中间值作为卷积后的结果值
-fn zhongzhilvbo() -> opencv::Result<()> {
+fn zhongzhilvbo(args: &ImageArgs) -> opencv::Result<()> {
     let mut new_mat = Mat::default();
 
-    let mat = imread("C:\\Users\\Administrator\\Desktop\\3.png", IMREAD_COLOR)?;
+    let mat = imread(&args.input, IMREAD_COLOR)?;
 
     median_blur(&mat, &mut new_mat, 5)?;
 
-    imshow("asdsa", &new_mat)?;
-
-    wait_key(10000)?;
+    args.show("asdsa", &new_mat)?;
 
     Ok(())
 }
 
 /// 低通 - 双边滤波(美颜, 保留边缘)
-fn shuangbianlvbo() -> opencv::Result<()> {
+fn shuangbianlvbo(args: &ImageArgs) -> opencv::Result<()> {
     let mut new_mat = Mat::default();
 
-    let mat = imread("C:\\Users\\Administrator\\Desktop\\lena.png", IMREAD_COLOR)?;
+    let mat = imread(&args.input, IMREAD_COLOR)?;
 
     bilateral_filter(&mat, &mut new_mat, 7, 20f64, 50f64, BORDER_DEFAULT)?;
 
-    imshow("asdsa3", &mat)?;
-
-    wait_key(10000)?;
+    args.preview("asdsa3", &mat)?;
 
-    imshow("asdsa", &new_mat)?;
-
-    wait_key(10000)?;
+    args.show("asdsa", &new_mat)?;
 
     Ok(())
 }
@@ -252,11 +234,11 @@ fn shuangbianlvbo() -> opencv::Result<()> {
 /// 高通 - 索贝尔(x, y都要求一遍, 然后再相加)
 ///
 /// kisize设置成-1会变成Scharr算法, 能识别更细小的线
-fn suobeier() -> opencv::Result<()> {
+fn suobeier(args: &ImageArgs) -> opencv::Result<()> {
     let mut x = Mat::default();
     let mut y = Mat::default();
 
-    let raw_mat = imread("C:\\Users\\Administrator\\Desktop\\chess.png", IMREAD_COLOR)?;
+    let raw_mat = imread(&args.input, IMREAD_COLOR)?;
 
     sobel(&raw_mat, &mut x, -1, 0, 1, 5, 1f64, 0f64, BORDER_DEFAULT)?;
     sobel(&raw_mat, &mut y, -1, 1, 0, 5, 1f64, 0f64, BORDER_DEFAULT)?;
@@ -264,9 +246,7 @@ fn suobeier() -> opencv::Result<()> {
     let mut new_mat = Mat::default();
     add(&x, &y, &mut new_mat, &no_array(), -1)?;
 
-    imshow("asdsa3", &new_mat)?;
-
-    wait_key(10000)?;
+    args.show("asdsa3", &new_mat)?;
 
     Ok(())
 }
@@ -274,16 +254,14 @@ fn suobeier() -> opencv::Result<()> {
 /// 高通 - 拉普拉斯算子
 ///
 /// 可同时对x, y进行推导, 缺点是对噪音敏感, 一般需要先进行去噪之后再使用拉普拉斯
-fn lapulasi() -> opencv::Result<()> {
+fn lapulasi(args: &ImageArgs) -> opencv::Result<()> {
     let mut new_mat = Mat::default();
 
-    let raw_mat = imread("C:\\Users\\Administrator\\Desktop\\chess.png", IMREAD_COLOR)?;
+    let raw_mat = imread(&args.input, IMREAD_COLOR)?;
 
     laplacian(&raw_mat, &mut new_mat, -1, 1, 1f64, 0f64, BORDER_DEFAULT)?;
 
-    imshow("asdsa3", &new_mat)?;
-
-    wait_key(10000)?;
+    args.show("asdsa3", &new_mat)?;
 
     Ok(())
 }
@@ -297,32 +275,63 @@ fn lapulasi() -> opencv::Result<()> {
 ///取局部最大值
 ///
 /// 阈值计算
-fn canny_bianyuanjiance() -> opencv::Result<()> {
+fn canny_bianyuanjiance(args: &ImageArgs) -> opencv::Result<()> {
     let mut new_mat = Mat::default();
 
-    let raw_mat = imread("C:\\Users\\Administrator\\Desktop\\chess.png", IMREAD_COLOR)?;
+    let raw_mat = imread(&args.input, IMREAD_COLOR)?;
 
     canny(&raw_mat, &mut new_mat, 200f64, 400f64, 3, false)?;
 
-    imshow("asdsa3", &new_mat)?;
-
-    wait_key(10000)?;
+    args.show("asdsa3", &new_mat)?;
 
     Ok(())
 }
 
-fn main() -> opencv::Result<()> {
-    // resize_func()?;
-    // fangshebianhuan()?;
-    // toushibianhuan()?;
-    // juanji()?;
-    // junzhilvbo()?;
-    // gaosilvbo()?;
-    // zhongzhilvbo()?;
-    // shuangbianlvbo()?;
-    // suobeier()?;
-    // lapulasi()?;
-    canny_bianyuanjiance()?;
+#[derive(Parser)]
+#[command(about = "图像变换和滤波")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-    Ok(())
+#[derive(Subcommand)]
+enum Command {
+    /// 放大缩小图片
+    Resize(ImageArgs),
+    /// 仿射变换
+    Affine(ImageArgs),
+    /// 透视变换
+    Perspective(ImageArgs),
+    /// 低通滤波(自定义卷积核)
+    Filter2d(ImageArgs),
+    /// 低通 - 均值滤波
+    Blur(ImageArgs),
+    /// 低通 - 高斯滤波
+    Gaussian(ImageArgs),
+    /// 低通 - 中值滤波
+    Median(ImageArgs),
+    /// 低通 - 双边滤波
+    Bilateral(ImageArgs),
+    /// 高通 - 索贝尔
+    Sobel(ImageArgs),
+    /// 高通 - 拉普拉斯算子
+    Laplacian(ImageArgs),
+    /// Canny边缘检测
+    Canny(ImageArgs),
+}
+
+fn main() -> opencv::Result<()> {
+    match Cli::parse().command {
+        Command::Resize(args) => resize_func(&args),
+        Command::Affine(args) => fangshebianhuan(&args),
+        Command::Perspective(args) => toushibianhuan(&args),
+        Command::Filter2d(args) => juanji(&args),
+        Command::Blur(args) => junzhilvbo(&args),
+        Command::Gaussian(args) => gaosilvbo(&args),
+        Command::Median(args) => zhongzhilvbo(&args),
+        Command::Bilateral(args) => shuangbianlvbo(&args),
+        Command::Sobel(args) => suobeier(&args),
+        Command::Laplacian(args) => lapulasi(&args),
+        Command::Canny(args) => canny_bianyuanjiance(&args),
+    }
 }
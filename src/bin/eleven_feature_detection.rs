@@ -1,3 +1,5 @@
+use clap::{Parser, Subcommand};
+use cv_demo::cli::{ImageArgs, PairArgs};
 use opencv::calib3d::{find_homography, RANSAC};
 use opencv::core::{no_array, perspective_transform, Point2f};
 use opencv::features2d::{
@@ -5,7 +7,6 @@ use opencv::features2d::{
     SIFT,
 };
 use opencv::flann::{IndexParams, SearchParams, FLANN_INDEX_KDTREE};
-use opencv::highgui::{imshow, wait_key};
 use opencv::imgcodecs::imread_def;
 use opencv::imgproc::{cvt_color_def, COLOR_BGR2GRAY};
 use opencv::prelude::*;
@@ -23,8 +24,8 @@ use std::process::exit;
 /// 缺点: 效率慢
 ///
 /// 描述子, 记录了关键点周围对其有贡献的像素点的一组向量值, 其不受仿射变换, 光照变换 等影响
-fn sift_function() -> opencv::Result<()> {
-    let raw_mat = imread_def("C:\\Users\\Administrator\\Desktop\\chess.png")?;
+fn sift_function(args: &ImageArgs) -> opencv::Result<()> {
+    let raw_mat = imread_def(&args.input)?;
 
     // sift需要灰度化
     let mut gray = Mat::default();
@@ -45,9 +46,7 @@ fn sift_function() -> opencv::Result<()> {
     let mut kp_mat = Mat::default();
     draw_keypoints_def(&raw_mat, &key_point, &mut kp_mat)?;
 
-    imshow("ssd", &kp_mat)?;
-
-    wait_key(10000)?;
+    args.show("ssd", &kp_mat)?;
 
     Ok(())
 }
@@ -57,8 +56,8 @@ fn sift_function() -> opencv::Result<()> {
 /// 优点: 检测速度快
 ///
 /// 缺点: 准确性没有SIFT高
-fn surf_function() -> opencv::Result<()> {
-    let raw_mat = imread_def("C:\\Users\\Administrator\\Desktop\\chess.png")?;
+fn surf_function(args: &ImageArgs) -> opencv::Result<()> {
+    let raw_mat = imread_def(&args.input)?;
 
     // sift需要灰度化
     let mut gray = Mat::default();
@@ -79,9 +78,7 @@ fn surf_function() -> opencv::Result<()> {
     let mut kp_mat = Mat::default();
     draw_keypoints_def(&raw_mat, &key_point, &mut kp_mat)?;
 
-    imshow("ssd", &kp_mat)?;
-
-    wait_key(10000)?;
+    args.show("ssd", &kp_mat)?;
 
     Ok(())
 }
@@ -91,8 +88,8 @@ fn surf_function() -> opencv::Result<()> {
 /// 优点: 可以做到实时检测
 ///
 /// 缺点: 对描述子的数据量进行缩减, 准确性不如SURF和SIFT
-fn orb_function() -> opencv::Result<()> {
-    let raw_mat = imread_def("C:\\Users\\Administrator\\Desktop\\chess.png")?;
+fn orb_function(args: &ImageArgs) -> opencv::Result<()> {
+    let raw_mat = imread_def(&args.input)?;
 
     // sift需要灰度化
     let mut gray = Mat::default();
@@ -113,9 +110,7 @@ fn orb_function() -> opencv::Result<()> {
     let mut kp_mat = Mat::default();
     draw_keypoints_def(&raw_mat, &key_point, &mut kp_mat)?;
 
-    imshow("ssd", &kp_mat)?;
-
-    wait_key(10000)?;
+    args.show("ssd", &kp_mat)?;
 
     Ok(())
 }
@@ -127,9 +122,9 @@ fn orb_function() -> opencv::Result<()> {
 /// 原理是, 将A的关键件和描述和B的关键点和描述子进行遍历匹配
 ///
 /// 计算它们之间的差距, 然后将最接近的一个匹配返回
-fn bf_function() -> opencv::Result<()> {
-    let src_mat = imread_def("C:\\Users\\Administrator\\Desktop\\111.png")?;
-    let dst_mat = imread_def("C:\\Users\\Administrator\\Desktop\\222.png")?;
+fn bf_function(args: &PairArgs) -> opencv::Result<()> {
+    let src_mat = imread_def(&args.src)?;
+    let dst_mat = imread_def(&args.dst)?;
 
     // sift需要灰度化
     let mut src_gray = Mat::default();
@@ -176,9 +171,7 @@ fn bf_function() -> opencv::Result<()> {
         &mut net_mat,
     )?;
 
-    imshow("ssd", &net_mat)?;
-
-    wait_key(10000)?;
+    args.show("ssd", &net_mat)?;
 
     Ok(())
 }
@@ -188,9 +181,9 @@ fn bf_function() -> opencv::Result<()> {
 /// 优点: 效率快, 批量最适合
 ///
 /// 缺点: 匹配不精准
-fn flann_function() -> opencv::Result<()> {
-    let src_mat = imread_def("C:\\Users\\Administrator\\Desktop\\opencv_search.png")?;
-    let dst_mat = imread_def("C:\\Users\\Administrator\\Desktop\\opencv_orig.png")?;
+fn flann_function(args: &PairArgs) -> opencv::Result<()> {
+    let src_mat = imread_def(&args.src)?;
+    let dst_mat = imread_def(&args.dst)?;
 
     // sift需要灰度化
     let mut src_gray = Mat::default();
@@ -264,9 +257,7 @@ fn flann_function() -> opencv::Result<()> {
         &mut net_mat,
     )?;
 
-    imshow("ssd", &net_mat)?;
-
-    wait_key(100000)?;
+    args.show("ssd", &net_mat)?;
 
     Ok(())
 }
@@ -274,9 +265,9 @@ fn flann_function() -> opencv::Result<()> {
 /// 单应型矩阵
 ///
 /// 一个图片在不同视角有不同维度, 经过某一点可计算出另一点的位置
-fn dan_ying_xing_nv_zhen() -> opencv::Result<()> {
-    let src_mat = imread_def("C:\\Users\\Administrator\\Desktop\\opencv_search.png")?;
-    let mut dst_mat = imread_def("C:\\Users\\Administrator\\Desktop\\opencv_orig.png")?;
+fn dan_ying_xing_nv_zhen(args: &PairArgs) -> opencv::Result<()> {
+    let src_mat = imread_def(&args.src)?;
+    let dst_mat = imread_def(&args.dst)?;
 
     // sift需要灰度化
     let mut src_gray = Mat::default();
@@ -380,9 +371,7 @@ fn dan_ying_xing_nv_zhen() -> opencv::Result<()> {
         //     &mut net_mat,
         // )?;
         //
-        imshow("ssd", &h)?;
-
-        wait_key(100000)?;
+        args.show("ssd", &h)?;
     } else {
         println!("数组长度不能小于4个");
         exit(0)
@@ -391,13 +380,36 @@ fn dan_ying_xing_nv_zhen() -> opencv::Result<()> {
     Ok(())
 }
 
-fn main() -> opencv::Result<()> {
-    // sift_function()?;
-    // surf_function()?;
-    // orb_function()?;
-    // bf_function()?;
-    // flann_function()?;
-    dan_ying_xing_nv_zhen()?;
+#[derive(Parser)]
+#[command(about = "特征检测和匹配")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-    Ok(())
+#[derive(Subcommand)]
+enum Command {
+    /// SIFT特征检测
+    Sift(ImageArgs),
+    /// SURF特征检测
+    Surf(ImageArgs),
+    /// ORB特征检测
+    Orb(ImageArgs),
+    /// Brute-Force 暴力特征匹配
+    Bf(PairArgs),
+    /// FLANN 最快近邻区特征匹配
+    Flann(PairArgs),
+    /// 单应性矩阵
+    Homography(PairArgs),
+}
+
+fn main() -> opencv::Result<()> {
+    match Cli::parse().command {
+        Command::Sift(args) => sift_function(&args),
+        Command::Surf(args) => surf_function(&args),
+        Command::Orb(args) => orb_function(&args),
+        Command::Bf(args) => bf_function(&args),
+        Command::Flann(args) => flann_function(&args),
+        Command::Homography(args) => dan_ying_xing_nv_zhen(&args),
+    }
 }
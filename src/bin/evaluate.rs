@@ -0,0 +1,109 @@
+use std::error::Error;
+
+use clap::Parser;
+use cv_demo::cli::{parse_fps, parse_iou};
+use cv_demo::eval::{evaluate, GroundTruth};
+use cv_demo::PipelineConfig;
+
+#[derive(Parser)]
+#[command(about = "用标注数据评估检测, 跟踪和计数的准确率")]
+struct Cli {
+    /// 输入: 视频文件, 图片目录或者通配符(frames/*.png)
+    #[arg(short, long)]
+    input: String,
+
+    /// 覆盖输入源的帧率, 图片序列默认按25帧
+    #[arg(long, value_parser = parse_fps)]
+    fps: Option<f64>,
+
+    /// 标注文件, `.json` 按 JSON 读, 其他按 MOT 格式读
+    #[arg(short, long)]
+    ground_truth: String,
+
+    /// 过线事件的标注(JSON), 标注文件里已经有事件时可以不传
+    #[arg(short, long)]
+    events: Option<String>,
+
+    /// 配置文件路径(.toml / .json), 不传就用默认配置
+    #[arg(short, long)]
+    config: Option<String>,
+
+    /// 检测框和标注框的交并比超过多少算匹配上
+    #[arg(long, default_value_t = 0.5, value_parser = parse_iou)]
+    iou: f64,
+
+    /// 这次评估的名字, 默认用配置文件路径
+    #[arg(short, long)]
+    label: Option<String>,
+
+    /// 完整结果(包括配置)写成 JSON
+    #[arg(short, long)]
+    report: Option<String>,
+
+    /// 往这个 CSV 追加一行结果, 方便对比不同配置
+    #[arg(long)]
+    history: Option<String>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+
+    let config = match &cli.config {
+        Some(path) => PipelineConfig::load(path)?,
+        None => PipelineConfig::default(),
+    };
+
+    let mut truth = GroundTruth::load(&cli.ground_truth)?;
+    if let Some(path) = &cli.events {
+        truth.load_events(path)?;
+    }
+    if truth.last_frame().is_none() {
+        return Err(format!("{} has no annotated frames", cli.ground_truth).into());
+    }
+
+    let label = cli
+        .label
+        .or(cli.config)
+        .unwrap_or_else(|| "default".to_string());
+    let report = evaluate(&cli.input, cli.fps, &config, &truth, cli.iou, &label)?;
+
+    let detection = &report.detection;
+    let tracking = &report.tracking;
+    let counting = &report.counting;
+    println!("{}: {} frames", report.label, report.frames);
+    println!(
+        "detection: precision {:.3}, recall {:.3}, f1 {:.3} (tp {}, fp {}, fn {})",
+        detection.precision,
+        detection.recall,
+        detection.f1,
+        detection.true_positives,
+        detection.false_positives,
+        detection.false_negatives
+    );
+    println!(
+        "tracking: mota {:.3}, idf1 {:.3} (misses {}, fp {}, id switches {})",
+        tracking.mota,
+        tracking.idf1,
+        tracking.misses,
+        tracking.false_positives,
+        tracking.id_switches
+    );
+    println!(
+        "counting: truth {}/{}, predicted {}/{} (down/up), error {} ({:.1}%)",
+        counting.truth.down,
+        counting.truth.up,
+        counting.predicted.down,
+        counting.predicted.up,
+        counting.error,
+        counting.relative_error * 100f64
+    );
+
+    if let Some(path) = &cli.report {
+        report.write_json(path)?;
+    }
+    if let Some(path) = &cli.history {
+        report.append_csv(path)?;
+    }
+
+    Ok(())
+}
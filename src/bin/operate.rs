@@ -1,27 +1,28 @@
+use clap::{Parser, Subcommand};
+use cv_demo::cli::{ImageArgs, OutputArgs};
 use opencv::core::{
     add, bitwise_and, bitwise_not, bitwise_or, bitwise_xor, no_array, Rect, Scalar, CV_8UC1,
     CV_8UC3,
 };
-use opencv::highgui::{imshow, wait_key};
 use opencv::imgcodecs::{imread, IMREAD_COLOR};
 use opencv::prelude::*;
 
 fn roi_3_channel() -> opencv::Result<()> {
-    // ROI 赋值
+    // ROI 赋值3通道
     let mat = Mat::new_rows_cols_with_default(200, 200, CV_8UC1, 0.into())?;
     Mat::roi(&mat, Rect::new(50, 50, 100, 100))?.set_scalar((0, 0, 0).into())?;
     Ok(())
 }
 
 fn roi_1_channel() -> opencv::Result<()> {
-    // ROI 赋值3通道
+    // ROI 赋值单通道
     let mat = Mat::new_rows_cols_with_default(200, 200, CV_8UC1, 0.into())?;
     Mat::roi(&mat, Rect::new(50, 50, 100, 100))?.set_scalar(Scalar::from(255))?;
     Ok(())
 }
 
 // 非运算(取反)
-fn wise_not() -> opencv::Result<()> {
+fn wise_not(args: &OutputArgs) -> opencv::Result<()> {
     // ROI 赋值3通道
     let mat = Mat::new_rows_cols_with_default(200, 200, CV_8UC1, 0.into())?;
     Mat::roi(&mat, Rect::new(50, 50, 100, 100))?.set_scalar(Scalar::from(255))?;
@@ -30,15 +31,13 @@ fn wise_not() -> opencv::Result<()> {
 
     bitwise_not(&mat, &mut ddd, &no_array()).unwrap();
 
-    imshow("adasd", &ddd).unwrap();
-
-    wait_key(10000).unwrap();
+    args.show("adasd", &ddd)?;
 
     Ok(())
 }
 
 // 与运算(取交集)(同时为1, 则为真)
-fn wise_and() -> opencv::Result<()> {
+fn wise_and(args: &OutputArgs) -> opencv::Result<()> {
     // ROI 赋值3通道
     let one = Mat::new_rows_cols_with_default(200, 200, CV_8UC1, 0.into())?;
     Mat::roi(&one, Rect::new(20, 20, 120, 120))?.set_scalar(Scalar::from(255))?;
@@ -50,15 +49,13 @@ fn wise_and() -> opencv::Result<()> {
 
     bitwise_and(&one, &two, &mut final_mat, &no_array()).unwrap();
 
-    imshow("adasd", &final_mat).unwrap();
-
-    wait_key(10000).unwrap();
+    args.show("adasd", &final_mat)?;
 
     Ok(())
 }
 
 // 或运算(取两个集合的所有值)
-fn wise_or() -> opencv::Result<()> {
+fn wise_or(args: &OutputArgs) -> opencv::Result<()> {
     // ROI 赋值3通道
     let one = Mat::new_rows_cols_with_default(200, 200, CV_8UC1, 0.into())?;
     Mat::roi(&one, Rect::new(20, 20, 120, 120))?.set_scalar(Scalar::from(255))?;
@@ -70,15 +67,13 @@ fn wise_or() -> opencv::Result<()> {
 
     bitwise_or(&one, &two, &mut final_mat, &no_array()).unwrap();
 
-    imshow("adasd", &final_mat).unwrap();
-
-    wait_key(10000).unwrap();
+    args.show("adasd", &final_mat)?;
 
     Ok(())
 }
 
 // 异或运算(交集部分为0 , 不交集地方为1)
-fn wise_xor() -> opencv::Result<()> {
+fn wise_xor(args: &OutputArgs) -> opencv::Result<()> {
     // ROI 赋值3通道
     let one = Mat::new_rows_cols_with_default(200, 200, CV_8UC1, 0.into())?;
     Mat::roi(&one, Rect::new(20, 20, 120, 120))?.set_scalar(Scalar::from(255))?;
@@ -90,14 +85,12 @@ fn wise_xor() -> opencv::Result<()> {
 
     bitwise_xor(&one, &two, &mut final_mat, &no_array()).unwrap();
 
-    imshow("adasd", &final_mat).unwrap();
-
-    wait_key(10000).unwrap();
+    args.show("adasd", &final_mat)?;
 
     Ok(())
 }
 
-fn add_logo() -> opencv::Result<()> {
+fn add_logo(args: &ImageArgs) -> opencv::Result<()> {
     let logo = Mat::new_rows_cols_with_default(200, 200, CV_8UC3, 0.into())?;
     Mat::roi(&logo, Rect::new(20, 20, 120, 120))?.set_scalar((0, 0, 255).into())?;
     Mat::roi(&logo, Rect::new(60, 60, 120, 120))?.set_scalar((0, 255, 0).into())?;
@@ -111,7 +104,7 @@ fn add_logo() -> opencv::Result<()> {
     // 翻转颜色, 这样黑底就暴漏出来了
     bitwise_not(&mask, &mut new_mask, &no_array())?;
 
-    let mut dog_mat = imread("C:\\Users\\Administrator\\Desktop\\dog.jpeg", IMREAD_COLOR)?;
+    let dog_mat = imread(&args.input, IMREAD_COLOR)?;
     let roi = Mat::roi(&dog_mat, Rect::new(0, 0, 200, 200))?;
 
     let mut tmp = Mat::default();
@@ -123,15 +116,44 @@ fn add_logo() -> opencv::Result<()> {
 
     Mat::roi(&dog_mat, Rect::new(0, 0, 200, 200))?.set(&new_logo)?;
 
-    imshow("asdasd", &dog_mat).unwrap();
-
-    wait_key(10000)?;
+    args.show("asdasd", &dog_mat)?;
 
     Ok(())
 }
 
-fn main() -> opencv::Result<()> {
-    add_logo()?;
+#[derive(Parser)]
+#[command(about = "ROI和位运算")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-    Ok(())
+#[derive(Subcommand)]
+enum Command {
+    /// ROI 赋值3通道
+    Roi3Channel,
+    /// ROI 赋值单通道
+    Roi1Channel,
+    /// 非运算(取反)
+    Not(OutputArgs),
+    /// 与运算(取交集)
+    And(OutputArgs),
+    /// 或运算(取两个集合的所有值)
+    Or(OutputArgs),
+    /// 异或运算
+    Xor(OutputArgs),
+    /// 在图片左上角加logo
+    AddLogo(ImageArgs),
+}
+
+fn main() -> opencv::Result<()> {
+    match Cli::parse().command {
+        Command::Roi3Channel => roi_3_channel(),
+        Command::Roi1Channel => roi_1_channel(),
+        Command::Not(args) => wise_not(&args),
+        Command::And(args) => wise_and(&args),
+        Command::Or(args) => wise_or(&args),
+        Command::Xor(args) => wise_xor(&args),
+        Command::AddLogo(args) => add_logo(&args),
+    }
 }
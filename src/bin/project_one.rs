@@ -1,145 +1,273 @@
-use opencv::bgsegm::create_background_subtractor_mog_def;
-use opencv::core::{Point, Rect, Scalar, Size, BORDER_CONSTANT, BORDER_DEFAULT};
-use opencv::highgui::{destroy_all_windows, imshow, wait_key};
-use opencv::imgproc::{
-    bounding_rect, cvt_color_def, dilate, erode_def, find_contours_def, gaussian_blur_def,
-    get_structuring_element_def, line_def, morphology_ex_def, put_text_def, rectangle_def,
-    CHAIN_APPROX_SIMPLE, COLOR_BGR2GRAY, FONT_HERSHEY_SIMPLEX, MORPH_CLOSE, MORPH_RECT, RETR_TREE,
-};
-use opencv::prelude::*;
-use opencv::types::VectorOfVectorOfPoint;
-use opencv::videoio::VideoCapture;
-
-const MIN_W: i32 = 90;
-const MIN_H: i32 = 90;
-
-const LINE_HIGH: i32 = 550;
-
-const LINE_OFFSET: i32 = 7;
-
-fn center(point: &Rect) -> Point {
-    let x = point.width / 2;
-    let y = point.height / 2;
-
-    let center_x = point.x + x;
-    let center_y = point.y + y;
-
-    Point {
-        x: center_x,
-        y: center_y,
-    }
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+
+use clap::Parser;
+use cv_demo::cli::{parse_fps, parse_interval, OutputArgs};
+use cv_demo::config::DetectorBackend;
+use cv_demo::export::{record_sink, IncidentLog, IncidentRecord};
+use cv_demo::output::{file_sink, FrameSink, Sinks, WindowSink};
+use cv_demo::snapshot::{ImageFormat, SnapshotWriter};
+use cv_demo::source::{open_source, Frame};
+use cv_demo::stats::TrafficStats;
+use cv_demo::{FrameRecord, PipelineConfig, VehicleCounter};
+
+#[derive(Parser)]
+#[command(about = "车辆计数")]
+struct Cli {
+    /// 输入: 视频文件, 图片目录或者通配符(frames/*.png), 摄像头编号, 视频流地址(rtsp://...)
+    #[arg(short, long)]
+    input: String,
+
+    /// 覆盖输入源的帧率, 图片序列默认按25帧
+    #[arg(long, value_parser = parse_fps)]
+    fps: Option<f64>,
+
+    /// 配置文件路径(.toml / .json), 不传就用默认配置
+    #[arg(short, long)]
+    config: Option<String>,
+
+    // 标注后的视频(.mp4 / .avi), 图片, 或者图片序列目录
+    #[command(flatten)]
+    output: OutputArgs,
+
+    /// 逐帧检测结果保存路径, `.csv` 写 CSV, 其他写 JSON Lines
+    #[arg(short, long)]
+    records: Option<String>,
+
+    /// 按时间段汇总的流量, 占有率和平均车速, `.csv` 写 CSV, 其他写 JSON
+    #[arg(long)]
+    stats: Option<String>,
+
+    /// 汇总的时间段长度, 分钟, 可以写多个: 1,5,15
+    #[arg(long, value_delimiter = ',', default_value = "5", value_parser = parse_interval)]
+    stats_interval: Vec<f64>,
+
+    /// 停车, 逆行和排队事件的日志, JSON Lines
+    #[arg(long)]
+    incidents: Option<String>,
+
+    /// 事件和计数车辆的截图保存到这个目录, 路径记在事件日志和逐帧结果里
+    #[arg(long)]
+    snapshots: Option<String>,
+
+    /// 截图格式
+    #[arg(long, value_enum, default_value_t = ImageFormat::Jpg)]
+    snapshot_format: ImageFormat,
+
+    /// 计数时再存一张画上车辆外框的整帧截图
+    #[arg(long)]
+    full_frame_snapshots: bool,
+
+    /// 把被过滤掉的轮廓和原因打印到 stderr
+    #[arg(long)]
+    log_rejected: bool,
+
+    /// 每隔几帧处理一帧, 1表示每帧都处理
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    every: u64,
 }
 
-fn main() -> opencv::Result<()> {
-    let mut capture = VideoCapture::from_file_def("C:\\Users\\Administrator\\Desktop\\video.mp4")?;
+/// 快照存不下来时只打印错误, 不中断处理, 事件里也不记路径
+fn snapshot_path(frame_index: u64, saved: opencv::Result<PathBuf>) -> Option<String> {
+    match saved {
+        Ok(path) => Some(path.to_string_lossy().into_owned()),
+        Err(err) => {
+            eprintln!("frame {}: cannot save snapshot: {}", frame_index, err);
+            None
+        }
+    }
+}
 
-    // 去后背景, 参数history:200, 如果视频是25帧, 大概取8帧图片作为历史, 只有像素点发生移动才会认为是前背景
-    let mut bgsubmog = create_background_subtractor_mog_def()?;
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+
+    let config = match &cli.config {
+        Some(path) => PipelineConfig::load(path)?,
+        None => PipelineConfig::default(),
+    };
+
+    // 去背景会把停下的车慢慢学进背景, 轨迹丢掉之后就报不出停车和排队
+    let rules = &config.incidents;
+    if config.detector.backend == DetectorBackend::Motion
+        && (rules.stopped.is_some() || rules.queue.is_some())
+    {
+        eprintln!(
+            "warning: stopped and queue incidents need detector.backend = \"dnn\", \
+             the motion backend loses vehicles once they stop"
+        );
+    }
 
-    let kernel = get_structuring_element_def(MORPH_RECT, Size::new(5, 5))?;
+    let mut source = open_source(&cli.input, cli.fps)?;
+    let fps = source.fps();
+    let size = source.size();
+    eprintln!(
+        "{}: {}x{} @ {:.2} fps",
+        cli.input, size.width, size.height, fps
+    );
 
-    let mut cars = Vec::new();
+    let mut counter = VehicleCounter::from_config(&config)?;
+    counter.set_fps(fps);
 
-    let mut car_num = 0;
+    // 跳帧之后输出视频的帧率和窗口刷新间隔都跟着变
+    let output_fps = fps / cli.every as f64;
 
-    loop {
-        // 读取帧
-        let mut frame = Mat::default();
-        let ret = capture.read(&mut frame)?;
+    let mut sinks = Sinks::default();
+    if let Some(path) = &cli.output.output {
+        sinks.push_boxed(file_sink(path, output_fps)?);
+    }
+    // 只要结果写到了文件里就不弹窗口, 方便在服务器上跑
+    let other_files = cli.records.is_some()
+        || cli.stats.is_some()
+        || cli.incidents.is_some()
+        || cli.snapshots.is_some();
+    if cli.output.displaying_with(other_files) {
+        sinks.push(WindowSink::new("adas", (1000f64 / output_fps) as i32));
+    }
 
-        if !ret {
-            break;
+    let mut records = match &cli.records {
+        Some(path) => Some(record_sink(path)?),
+        None => None,
+    };
+
+    let mut incidents = match &cli.incidents {
+        Some(path) => Some(IncidentLog::create(path)?),
+        None => None,
+    };
+    let snapshots = match &cli.snapshots {
+        Some(dir) => Some(SnapshotWriter::new(dir, cli.snapshot_format)?),
+        None => None,
+    };
+
+    let mut stats = cli
+        .stats
+        .as_ref()
+        .map(|_| TrafficStats::new(&cli.stats_interval));
+
+    // 读取帧
+    while let Some(Frame {
+        index: frame_index,
+        time,
+        image: mut frame,
+    }) = source.read()?
+    {
+        let result = counter.process_frame_at(&frame, time)?;
+
+        for event in &result.speeding {
+            eprintln!(
+                "frame {}: track {} over speed {:.1} km/h (limit {:.0})",
+                frame_index, event.track_id, event.speed, event.limit
+            );
         }
 
-        // 转灰度
-        let mut cvt_frame = Mat::default();
-        cvt_color_def(&frame, &mut cvt_frame, COLOR_BGR2GRAY)?;
-
-        // 去噪
-        let mut blur = Mat::default();
-        gaussian_blur_def(&cvt_frame, &mut blur, Size::new(3, 3), 5f64)?;
-
-        // 去背景
-        let mut mask = Mat::default();
-        bgsubmog.apply_def(&blur, &mut mask)?;
-
-        // 腐蚀(在去掉一些小噪点)
-        let mut erode_mat = Mat::default();
-        erode_def(&mask, &mut erode_mat, &kernel)?;
-
-        // 经过腐蚀, 噪点没有, 图片变小了, 在通过膨胀放大到原来
-        let mut dilate_mat = Mat::default();
-        dilate(
-            &erode_mat,
-            &mut dilate_mat,
-            &kernel,
-            Point::new(-1, -1),
-            3,
-            BORDER_CONSTANT,
-            Scalar::from(BORDER_DEFAULT),
-        )?;
-
-        // 闭运算,去掉物内部小块
-        let mut close_mat = Mat::default();
-        morphology_ex_def(&dilate_mat, &mut close_mat, MORPH_CLOSE, &kernel)?;
-        let mut close_mat_1 = Mat::default();
-        morphology_ex_def(&close_mat, &mut close_mat_1, MORPH_CLOSE, &kernel)?;
-
-        // 查找轮廓
-        let mut contours = VectorOfVectorOfPoint::new();
-        find_contours_def(&close_mat_1, &mut contours, RETR_TREE, CHAIN_APPROX_SIMPLE)?;
-
-        // 画一条检测线
-        line_def(
-            &mut frame,
-            Point::new(10, LINE_HIGH),
-            Point::new(1200, LINE_HIGH),
-            (0, 0, 255).into(),
-        )?;
-
-        for (index, list) in contours.iter().enumerate() {
-            let rect = bounding_rect(&list)?;
-
-            // 过滤不是车的物体
-            if rect.width < MIN_W && rect.height < MIN_H {
-                continue;
+        // 快照要在画标注之前截
+        for incident in &result.incidents {
+            let ids = incident
+                .track_ids
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join("-");
+            eprintln!(
+                "frame {}: {} track {}{}",
+                frame_index,
+                incident.kind.as_str(),
+                ids,
+                incident
+                    .location
+                    .as_ref()
+                    .map(|location| format!(" at {}", location))
+                    .unwrap_or_default()
+            );
+
+            let snapshot = match &snapshots {
+                Some(snapshots) => {
+                    let name = format!(
+                        "{}_{}_{:.0}",
+                        incident.kind.as_str(),
+                        ids,
+                        incident.time * 1000f64
+                    );
+                    snapshot_path(frame_index, snapshots.save(&frame, incident.rect, &name))
+                }
+                None => None,
+            };
+            if let Some(incidents) = &mut incidents {
+                incidents.write(&IncidentRecord::new(frame_index, incident, snapshot))?;
             }
+        }
 
-            // 获取车辆的中心点
-            let car_center_point = center(&rect);
-            cars.push(car_center_point);
+        if cli.log_rejected {
+            for rejection in &result.rejected {
+                eprintln!("frame {}: rejected {}", frame_index, rejection);
+            }
+        }
 
-            for car in &mut cars {
-                // 要有一条线,并且有范围, 上下6个像素
-                if car.y > (LINE_HIGH - LINE_OFFSET) && (car.y < (LINE_HIGH + LINE_OFFSET)) {
-                    car_num += 1;
-                    // cars.remove(1);
+        // 计数车辆的截图按轨迹编号和时间戳命名, 同一辆车在一帧里过了几条线也只存一份
+        let mut counted_snapshots = HashMap::new();
+        if let Some(snapshots) = &snapshots {
+            for event in &result.counted {
+                if counted_snapshots.contains_key(&event.track_id) {
+                    continue;
                 }
+                let Some(track) = result.tracks.iter().find(|t| t.id == event.track_id) else {
+                    continue;
+                };
+
+                let name = format!("{}_{:.0}", track.id, time * 1000f64);
+                let crop = snapshot_path(frame_index, snapshots.save(&frame, track.rect, &name));
+                let full = if cli.full_frame_snapshots {
+                    let name = format!("{}_frame", name);
+                    snapshot_path(frame_index, snapshots.save_frame(&frame, track.rect, &name))
+                } else {
+                    None
+                };
+                counted_snapshots.insert(track.id, (crop, full));
             }
+        }
 
-            rectangle_def(&mut frame, rect, Scalar::from((0, 0, 255)))?;
+        if let Some(records) = &mut records {
+            let mut record = FrameRecord::new(frame_index, time * 1000f64, &result);
+            for event in &mut record.events {
+                if let Some((crop, full)) = counted_snapshots.get(&event.track_id) {
+                    event.snapshot = crop.clone();
+                    event.frame_snapshot = full.clone();
+                }
+            }
+            records.write(&record)?;
         }
 
-        put_text_def(
-            &mut frame,
-            car_num.to_string().as_str(),
-            Point::new(500, 60),
-            FONT_HERSHEY_SIMPLEX,
-            1f64,
-            (0, 0, 255).into(),
-        )?;
+        if let Some(stats) = &mut stats {
+            stats.update(time, &result);
+        }
 
-        imshow("adas", &frame)?;
+        counter.draw(&mut frame, &result)?;
 
-        let key = wait_key(25)?;
-        if key > 0 && key != 255 {
+        if !sinks.write(&frame)? {
             break;
         }
+
+        // 跳过的帧尽量不解码
+        for _ in 1..cli.every {
+            if !source.skip()? {
+                break;
+            }
+        }
+    }
+
+    sinks.finish()?;
+
+    if let Some(records) = &mut records {
+        records.finish()?;
     }
 
-    capture.release()?;
+    if let Some(incidents) = &mut incidents {
+        incidents.finish()?;
+    }
 
-    destroy_all_windows()?;
+    if let (Some(stats), Some(path)) = (&stats, &cli.stats) {
+        stats.save(path)?;
+    }
 
     Ok(())
 }
@@ -1,31 +1,21 @@
-use opencv::core::{
-    no_array, Mat, Point, Point2f, Point2i, RotatedRect, Scalar, Size, Vec4i, Vector,
-    BORDER_CONSTANT, CV_8U,
-};
-use opencv::highgui::{imshow, wait_key};
+use clap::{Parser, Subcommand};
+use cv_demo::cli::ImageArgs;
+use opencv::core::{no_array, Mat, Point, Point2i, RotatedRect, Scalar, Vector};
 use opencv::imgcodecs::{imread, IMREAD_COLOR};
 use opencv::imgproc::{
-    adaptive_threshold, approx_poly_dp, arc_length, bounding_rect, box_points, canny, contour_area,
-    convex_hull_def, cvt_color, dilate, draw_contours, draw_contours_def, erode, find_contours,
-    find_contours_def, find_contours_with_hierarchy_def, get_structuring_element, line,
-    min_area_rect, morphology_ex, rectangle, threshold, ADAPTIVE_THRESH_GAUSSIAN_C,
-    CHAIN_APPROX_SIMPLE, COLOR_BGR2GRAY, LINE_8, MORPH_BLACKHAT, MORPH_GRADIENT, MORPH_RECT,
-    MORPH_TOPHAT, RETR_EXTERNAL, RETR_TREE, THRESH_BINARY, THRESH_BINARY_INV,
-};
-use opencv::prelude::*;
-use opencv::types::{
-    VectorOfBox, VectorOfPoint, VectorOfPoint2f, VectorOfPoint3f, VectorOfRange, VectorOfRect,
-    VectorOfRotatedRect, VectorOfScalar, VectorOfSize, VectorOfVec4f, VectorOfVec4i,
-    VectorOfVectorOfPoint, VectorOfVectorOff32, VectorOff32, VectorOff64,
+    arc_length, bounding_rect, contour_area, convex_hull_def, cvt_color, draw_contours,
+    draw_contours_def, find_contours, find_contours_with_hierarchy_def, line, min_area_rect,
+    rectangle, threshold, CHAIN_APPROX_SIMPLE, COLOR_BGR2GRAY, LINE_8, RETR_TREE, THRESH_BINARY,
 };
+use opencv::types::{VectorOfPoint, VectorOfPoint2f, VectorOfVec4i, VectorOfVectorOfPoint};
 
 /// 轮廓查找 + 绘制轮廓 + 面积计算 + 周长计算
 ///
 /// 必须要二值化
-fn cha_zhao_lun_kuo() -> opencv::Result<()> {
+fn cha_zhao_lun_kuo(args: &ImageArgs) -> opencv::Result<()> {
     let mut new_mat = Mat::default();
 
-    let mut raw_mat = imread("C:\\Users\\Administrator\\Desktop\\1.png", IMREAD_COLOR)?;
+    let mut raw_mat = imread(&args.input, IMREAD_COLOR)?;
 
     // 转成灰度图
     cvt_color(&raw_mat, &mut new_mat, COLOR_BGR2GRAY, 0)?;
@@ -71,9 +61,7 @@ fn cha_zhao_lun_kuo() -> opencv::Result<()> {
         Point::default(),
     )?;
 
-    imshow("adas", &raw_mat)?;
-
-    wait_key(10000)?;
+    args.show("adas", &raw_mat)?;
 
     Ok(())
 }
@@ -81,8 +69,8 @@ fn cha_zhao_lun_kuo() -> opencv::Result<()> {
 /// 多边形逼近(轮廓描边, 类似一个手掌包含指缝), 用于存放特征点
 ///
 /// 多边形凸包(轮廓描边, 类似游泳划水掌), 用于存放轮廓
-fn duo_bian_xing_bin_jin() -> opencv::Result<()> {
-    let mut raw_mat = imread("C:\\Users\\Administrator\\Desktop\\hand.png", IMREAD_COLOR)?;
+fn duo_bian_xing_bin_jin(args: &ImageArgs) -> opencv::Result<()> {
+    let mut raw_mat = imread(&args.input, IMREAD_COLOR)?;
 
     let mut cvt_mat = Mat::default();
 
@@ -161,9 +149,7 @@ fn duo_bian_xing_bin_jin() -> opencv::Result<()> {
         count += 1
     }
 
-    imshow("asdad", &raw_mat)?;
-
-    wait_key(10000)?;
+    args.show("asdad", &raw_mat)?;
 
     Ok(())
 }
@@ -171,11 +157,8 @@ fn duo_bian_xing_bin_jin() -> opencv::Result<()> {
 /// 最小矩阵    可以获得角度
 ///
 /// 最大矩阵
-fn zui_xiao_zui_da_jvzhen() -> opencv::Result<()> {
-    let mut raw_mat = imread(
-        "C:\\Users\\Administrator\\Desktop\\hello.jpeg",
-        IMREAD_COLOR,
-    )?;
+fn zui_xiao_zui_da_jvzhen(args: &ImageArgs) -> opencv::Result<()> {
+    let mut raw_mat = imread(&args.input, IMREAD_COLOR)?;
 
     let mut cvt_mat = Mat::default();
 
@@ -235,17 +218,32 @@ fn zui_xiao_zui_da_jvzhen() -> opencv::Result<()> {
     let max = bounding_rect(&contours)?;
     rectangle(&mut raw_mat, max, Scalar::from((0, 0, 255)), 1, LINE_8, 0)?;
 
-    imshow("asdad", &raw_mat)?;
-
-    wait_key(10000)?;
+    args.show("asdad", &raw_mat)?;
 
     Ok(())
 }
 
-fn main() -> opencv::Result<()> {
-    // cha_zhao_lun_kuo()?;
-    // duo_bian_xing_bin_jin()?;
-    zui_xiao_zui_da_jvzhen()?;
+#[derive(Parser)]
+#[command(about = "轮廓")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-    Ok(())
+#[derive(Subcommand)]
+enum Command {
+    /// 轮廓查找 + 绘制轮廓 + 面积计算 + 周长计算
+    Contours(ImageArgs),
+    /// 多边形凸包
+    Hull(ImageArgs),
+    /// 最小矩阵和最大矩阵
+    MinAreaRect(ImageArgs),
+}
+
+fn main() -> opencv::Result<()> {
+    match Cli::parse().command {
+        Command::Contours(args) => cha_zhao_lun_kuo(&args),
+        Command::Hull(args) => duo_bian_xing_bin_jin(&args),
+        Command::MinAreaRect(args) => zui_xiao_zui_da_jvzhen(&args),
+    }
 }
@@ -0,0 +1,162 @@
+use std::error::Error;
+
+use clap::Parser;
+use cv_demo::cli::{parse_fps, parse_iou};
+use cv_demo::eval::{evaluate, GroundTruth};
+use cv_demo::parallel;
+use cv_demo::tune::{describe, Objective, SearchSpace};
+use cv_demo::PipelineConfig;
+
+#[derive(Parser)]
+#[command(about = "在标注过的视频上搜索形态学和过滤参数, 找出评估结果最好的配置")]
+struct Cli {
+    /// 输入: 视频文件, 图片目录或者通配符(frames/*.png)
+    #[arg(short, long)]
+    input: String,
+
+    /// 覆盖输入源的帧率, 图片序列默认按25帧
+    #[arg(long, value_parser = parse_fps)]
+    fps: Option<f64>,
+
+    /// 标注文件, `.json` 按 JSON 读, 其他按 MOT 格式读
+    #[arg(short, long)]
+    ground_truth: String,
+
+    /// 过线事件的标注(JSON), 标注文件里已经有事件时可以不传
+    #[arg(short, long)]
+    events: Option<String>,
+
+    /// 基础配置, 没有搜索的参数用这里的值
+    #[arg(short, long)]
+    config: Option<String>,
+
+    /// 每个参数的候选值(.toml / .json), 不传就用内置的候选值
+    #[arg(short, long)]
+    space: Option<String>,
+
+    /// 随机搜索多少组参数, 不传就网格搜索所有组合
+    #[arg(long)]
+    random: Option<usize>,
+
+    /// 随机搜索的种子
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// 按哪个指标挑最好的配置
+    #[arg(short, long, value_enum, default_value_t = Objective::F1)]
+    metric: Objective,
+
+    /// 检测框和标注框的交并比超过多少算匹配上
+    #[arg(long, default_value_t = 0.5, value_parser = parse_iou)]
+    iou: f64,
+
+    /// 同时评估几组参数, 默认等于 CPU 核数
+    #[arg(short, long)]
+    jobs: Option<usize>,
+
+    /// 打印最好的几组参数
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+
+    /// 最好的配置保存到这里(.toml / .json)
+    #[arg(short, long)]
+    output: Option<String>,
+
+    /// 每组参数的结果追加到这个 CSV
+    #[arg(long)]
+    history: Option<String>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+
+    let base = match &cli.config {
+        Some(path) => PipelineConfig::load(path)?,
+        None => PipelineConfig::default(),
+    };
+    let space = match &cli.space {
+        Some(path) => SearchSpace::load(path)?,
+        None => SearchSpace::default(),
+    };
+
+    let mut truth = GroundTruth::load(&cli.ground_truth)?;
+    if let Some(path) = &cli.events {
+        truth.load_events(path)?;
+    }
+    if truth.last_frame().is_none() {
+        return Err(format!("{} has no annotated frames", cli.ground_truth).into());
+    }
+
+    let candidates = match cli.random {
+        Some(count) => space.random(&base, count, cli.seed),
+        None => space.grid(&base),
+    };
+    if candidates.is_empty() {
+        return Err("no valid parameter combination in the search space".into());
+    }
+    eprintln!(
+        "{} candidates ({} combinations in the search space)",
+        candidates.len(),
+        space.grid_size()
+    );
+
+    let results = parallel::map(
+        &candidates,
+        cli.jobs,
+        |config| {
+            let label = describe(config);
+            let result = evaluate(&cli.input, cli.fps, config, &truth, cli.iou, &label);
+            (label, result)
+        },
+        |finished, _, (label, result)| match result {
+            Ok(report) => eprintln!(
+                "[{}/{}] {}: {:.4}",
+                finished,
+                candidates.len(),
+                label,
+                cli.metric.score(report)
+            ),
+            Err(err) => eprintln!(
+                "[{}/{}] {}: failed: {}",
+                finished,
+                candidates.len(),
+                label,
+                err
+            ),
+        },
+    );
+
+    let mut reports: Vec<_> = results
+        .into_iter()
+        .filter_map(|(_, result)| result.ok())
+        .collect();
+    if reports.is_empty() {
+        return Err("every candidate failed".into());
+    }
+    reports.sort_by(|a, b| cli.metric.score(b).total_cmp(&cli.metric.score(a)));
+
+    println!(
+        "rank  {:>8}  parameters",
+        format!("{:?}", cli.metric).to_lowercase()
+    );
+    for (rank, report) in reports.iter().take(cli.top).enumerate() {
+        println!(
+            "{:>4}  {:>8.4}  {}",
+            rank + 1,
+            cli.metric.score(report),
+            report.label
+        );
+    }
+
+    if let Some(path) = &cli.history {
+        for report in &reports {
+            report.append_csv(path)?;
+        }
+    }
+    if let Some(path) = &cli.output {
+        reports[0].config.save(path)?;
+        eprintln!("best config saved to {}", path);
+    }
+
+    Ok(())
+}
@@ -1,18 +1,18 @@
+use clap::{Parser, Subcommand};
+use cv_demo::cli::ImageArgs;
 use opencv::core::{Mat, Point, Scalar, Size, BORDER_CONSTANT, CV_8U};
-use opencv::highgui::{imshow, wait_key};
 use opencv::imgcodecs::{imread, IMREAD_COLOR};
 use opencv::imgproc::{
-    adaptive_threshold, canny, cvt_color, dilate, erode, get_structuring_element, morphology_ex,
+    adaptive_threshold, cvt_color, dilate, erode, get_structuring_element, morphology_ex,
     threshold, ADAPTIVE_THRESH_GAUSSIAN_C, COLOR_BGR2GRAY, MORPH_BLACKHAT, MORPH_GRADIENT,
-    MORPH_RECT, MORPH_TOPHAT, THRESH_BINARY, THRESH_BINARY_INV,
+    MORPH_RECT, THRESH_BINARY,
 };
-use opencv::prelude::*;
 
 /// 二值化
-fn erzhihua() -> opencv::Result<()> {
+fn erzhihua(args: &ImageArgs) -> opencv::Result<()> {
     let mut new_mat = Mat::default();
 
-    let raw_mat = imread("C:\\Users\\Administrator\\Desktop\\1.png", IMREAD_COLOR)?;
+    let raw_mat = imread(&args.input, IMREAD_COLOR)?;
 
     // 转成灰度图
     cvt_color(&raw_mat, &mut new_mat, COLOR_BGR2GRAY, 0)?;
@@ -20,9 +20,7 @@ fn erzhihua() -> opencv::Result<()> {
     let mut new_mat_2 = Mat::default();
     threshold(&new_mat, &mut new_mat_2, 100f64, 200f64, THRESH_BINARY)?;
 
-    imshow("asdsa3", &new_mat_2)?;
-
-    wait_key(10000)?;
+    args.show("asdsa3", &new_mat_2)?;
 
     Ok(())
 }
@@ -30,10 +28,10 @@ fn erzhihua() -> opencv::Result<()> {
 /// 自适应阈值
 ///
 ///由于光照不均匀以及阴影存在, 只有一个阈值会使得在阴影处的白色被二值化成黑色
-fn zishiying() -> opencv::Result<()> {
+fn zishiying(args: &ImageArgs) -> opencv::Result<()> {
     let mut new_mat = Mat::default();
 
-    let raw_mat = imread("C:\\Users\\Administrator\\Desktop\\1.png", IMREAD_COLOR)?;
+    let raw_mat = imread(&args.input, IMREAD_COLOR)?;
 
     // 转成灰度图
     cvt_color(&raw_mat, &mut new_mat, COLOR_BGR2GRAY, 0)?;
@@ -49,9 +47,7 @@ fn zishiying() -> opencv::Result<()> {
         0f64,
     )?;
 
-    imshow("asdsa3", &new_mat_2)?;
-
-    wait_key(10000)?;
+    args.show("asdsa3", &new_mat_2)?;
 
     Ok(())
 }
@@ -59,8 +55,8 @@ fn zishiying() -> opencv::Result<()> {
 /// 腐蚀
 ///
 /// 将图片进行瘦身, 卷积核越大, 腐蚀越明显
-fn fushi() -> opencv::Result<()> {
-    let raw_mat = imread("C:\\Users\\Administrator\\Desktop\\j.png", IMREAD_COLOR)?;
+fn fushi(args: &ImageArgs) -> opencv::Result<()> {
+    let raw_mat = imread(&args.input, IMREAD_COLOR)?;
 
     let size = Mat::new_rows_cols_with_default(3, 3, CV_8U, Scalar::from(1))?;
 
@@ -75,22 +71,18 @@ fn fushi() -> opencv::Result<()> {
         Scalar::from(0),
     )?;
 
-    imshow("asdsa3", &new_mat_2)?;
-
-    wait_key(10000)?;
+    args.show("asdsa3", &new_mat_2)?;
 
-    imshow("asdsa23", &raw_mat)?;
-
-    wait_key(10000)?;
+    args.preview("asdsa23", &raw_mat)?;
 
     Ok(())
 }
 
 /// 用现成的卷积核
-fn juanjihe() -> opencv::Result<()> {
+fn juanjihe(args: &ImageArgs) -> opencv::Result<()> {
     let m = get_structuring_element(MORPH_RECT, Size::new(7, 7), Point::new(-1, -1))?;
 
-    let raw_mat = imread("C:\\Users\\Administrator\\Desktop\\j.png", IMREAD_COLOR)?;
+    let raw_mat = imread(&args.input, IMREAD_COLOR)?;
 
     let mut new_mat_2 = Mat::default();
     dilate(
@@ -103,13 +95,9 @@ fn juanjihe() -> opencv::Result<()> {
         Scalar::from(0),
     )?;
 
-    imshow("asdsa3", &new_mat_2)?;
-
-    wait_key(10000)?;
+    args.show("asdsa3", &new_mat_2)?;
 
-    imshow("asdsa23", &raw_mat)?;
-
-    wait_key(10000)?;
+    args.preview("asdsa23", &raw_mat)?;
 
     Ok(())
 }
@@ -117,10 +105,10 @@ fn juanjihe() -> opencv::Result<()> {
 /// 开运算(先腐蚀, 在膨胀) 去除大图形外的小图形,  如果噪点大, 那么卷积核也要变大
 ///
 /// 闭运算(先膨胀, 后腐蚀) 去除大图形内的小图形
-fn kaiyunsuan() -> opencv::Result<()> {
+fn kaiyunsuan(args: &ImageArgs) -> opencv::Result<()> {
     let m = get_structuring_element(MORPH_RECT, Size::new(7, 7), Point::new(-1, -1))?;
 
-    let raw_mat = imread("C:\\Users\\Administrator\\Desktop\\dotj.png", IMREAD_COLOR)?;
+    let raw_mat = imread(&args.input, IMREAD_COLOR)?;
 
     let mut new_mat = Mat::default();
     morphology_ex(
@@ -134,13 +122,9 @@ fn kaiyunsuan() -> opencv::Result<()> {
         Scalar::from(0),
     )?;
 
-    imshow("asdsa3", &new_mat)?;
-
-    wait_key(10000)?;
+    args.show("asdsa3", &new_mat)?;
 
-    imshow("asdsa23", &raw_mat)?;
-
-    wait_key(10000)?;
+    args.preview("asdsa23", &raw_mat)?;
 
     Ok(())
 }
@@ -148,10 +132,10 @@ fn kaiyunsuan() -> opencv::Result<()> {
 /// 梯度计算(原图 - 腐蚀)   求边缘
 ///
 /// 边缘的清晰与否和卷积核大小有关, 卷积核越小, 边缘越清晰
-fn tidujisuan() -> opencv::Result<()> {
+fn tidujisuan(args: &ImageArgs) -> opencv::Result<()> {
     let m = get_structuring_element(MORPH_RECT, Size::new(5, 5), Point::new(-1, -1))?;
 
-    let raw_mat = imread("C:\\Users\\Administrator\\Desktop\\j.png", IMREAD_COLOR)?;
+    let raw_mat = imread(&args.input, IMREAD_COLOR)?;
 
     let mut new_mat = Mat::default();
     morphology_ex(
@@ -165,13 +149,9 @@ fn tidujisuan() -> opencv::Result<()> {
         Scalar::from(0),
     )?;
 
-    imshow("asdsa3", &new_mat)?;
-
-    wait_key(10000)?;
+    args.show("asdsa3", &new_mat)?;
 
-    imshow("asdsa23", &raw_mat)?;
-
-    wait_key(10000)?;
+    args.preview("asdsa23", &raw_mat)?;
 
     Ok(())
 }
@@ -179,13 +159,10 @@ fn tidujisuan() -> opencv::Result<()> {
 /// 顶帽计算(原图 - 开运算)  获取大图形外的小图形
 ///
 /// 黑帽计算(原图 - 开运算)  获取大图形内的小图形
-fn dingmaojisuan() -> opencv::Result<()> {
+fn dingmaojisuan(args: &ImageArgs) -> opencv::Result<()> {
     let m = get_structuring_element(MORPH_RECT, Size::new(7, 7), Point::new(-1, -1))?;
 
-    let raw_mat = imread(
-        "C:\\Users\\Administrator\\Desktop\\dotinj.png",
-        IMREAD_COLOR,
-    )?;
+    let raw_mat = imread(&args.input, IMREAD_COLOR)?;
 
     let mut new_mat = Mat::default();
     // 顶帽计算, 卷积核必须要大, 在这张图里  tophat.png
@@ -212,25 +189,46 @@ fn dingmaojisuan() -> opencv::Result<()> {
         Scalar::from(0),
     )?;
 
-    imshow("asdsa3", &new_mat)?;
+    args.show("asdsa3", &new_mat)?;
 
-    wait_key(10000)?;
+    args.preview("asdsa23", &raw_mat)?;
 
-    imshow("asdsa23", &raw_mat)?;
+    Ok(())
+}
 
-    wait_key(10000)?;
+#[derive(Parser)]
+#[command(about = "二值化和形态学")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
 
-    Ok(())
+#[derive(Subcommand)]
+enum Command {
+    /// 二值化
+    Threshold(ImageArgs),
+    /// 自适应阈值
+    Adaptive(ImageArgs),
+    /// 腐蚀
+    Erode(ImageArgs),
+    /// 用现成的卷积核膨胀
+    Dilate(ImageArgs),
+    /// 开运算
+    Open(ImageArgs),
+    /// 梯度计算
+    Gradient(ImageArgs),
+    /// 顶帽 / 黑帽计算
+    Blackhat(ImageArgs),
 }
 
 fn main() -> opencv::Result<()> {
-    // erzhihua()?;
-    // zishiying()?;
-    // fushi()?;
-    // juanjihe()?;
-    // kaiyunsuan()?;
-    // tidujisuan()?;
-    dingmaojisuan()?;
-
-    Ok(())
+    match Cli::parse().command {
+        Command::Threshold(args) => erzhihua(&args),
+        Command::Adaptive(args) => zishiying(&args),
+        Command::Erode(args) => fushi(&args),
+        Command::Dilate(args) => juanjihe(&args),
+        Command::Open(args) => kaiyunsuan(&args),
+        Command::Gradient(args) => tidujisuan(&args),
+        Command::Blackhat(args) => dingmaojisuan(&args),
+    }
 }
@@ -0,0 +1,217 @@
+//! 按轮廓的形状过滤不是车的物体
+use std::fmt;
+
+use opencv::core::Rect;
+use opencv::imgproc::{bounding_rect, contour_area, convex_hull_def, min_area_rect};
+use opencv::types::VectorOfPoint;
+
+use crate::config::FilterConfig;
+
+/// 一个前景轮廓的形状特征
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Blob {
+    /// 外接矩形
+    pub rect: Rect,
+    /// 轮廓面积
+    pub area: f64,
+    /// 最小外接矩形的长边
+    pub length: f64,
+    /// 最小外接矩形的短边
+    pub width: f64,
+    /// 最小外接矩形的长边 / 短边, 不受车辆朝向影响
+    pub aspect: f64,
+    /// 轮廓面积 / 凸包面积, 越小越不规则
+    pub solidity: f64,
+    /// 轮廓面积 / 外接矩形面积
+    pub extent: f64,
+}
+
+impl Blob {
+    pub fn measure(contour: &VectorOfPoint) -> opencv::Result<Self> {
+        let rect = bounding_rect(contour)?;
+        let area = contour_area(contour, false)?;
+
+        let size = min_area_rect(contour)?.size();
+        let (length, width) = if size.width > size.height {
+            (size.width as f64, size.height as f64)
+        } else {
+            (size.height as f64, size.width as f64)
+        };
+        let aspect = if width > 0f64 {
+            length / width
+        } else {
+            f64::INFINITY
+        };
+
+        let mut hull = VectorOfPoint::new();
+        convex_hull_def(contour, &mut hull)?;
+        let hull_area = contour_area(&hull, false)?;
+        let solidity = if hull_area > 0f64 {
+            area / hull_area
+        } else {
+            0f64
+        };
+
+        let rect_area = (rect.width * rect.height) as f64;
+        let extent = if rect_area > 0f64 {
+            area / rect_area
+        } else {
+            0f64
+        };
+
+        Ok(Self {
+            rect,
+            area,
+            length,
+            width,
+            aspect,
+            solidity,
+            extent,
+        })
+    }
+}
+
+/// 轮廓被过滤掉的原因, 带上实际值和配置里的限制
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RejectReason {
+    /// 宽或者高太小
+    TooSmall {
+        width: i32,
+        height: i32,
+        min_w: i32,
+        min_h: i32,
+    },
+    AreaTooSmall {
+        area: f64,
+        min: f64,
+    },
+    AreaTooLarge {
+        area: f64,
+        max: f64,
+    },
+    /// 太细长或者太方
+    AspectOutOfRange {
+        aspect: f64,
+        min: f64,
+        max: f64,
+    },
+    SolidityTooLow {
+        solidity: f64,
+        min: f64,
+    },
+    ExtentTooLow {
+        extent: f64,
+        min: f64,
+    },
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectReason::TooSmall {
+                width,
+                height,
+                min_w,
+                min_h,
+            } => write!(f, "size {}x{} below {}x{}", width, height, min_w, min_h),
+            RejectReason::AreaTooSmall { area, min } => {
+                write!(f, "area {:.0} below min_area {:.0}", area, min)
+            }
+            RejectReason::AreaTooLarge { area, max } => {
+                write!(f, "area {:.0} above max_area {:.0}", area, max)
+            }
+            RejectReason::AspectOutOfRange { aspect, min, max } => {
+                write!(f, "aspect {:.2} outside [{:.2}, {:.2}]", aspect, min, max)
+            }
+            RejectReason::SolidityTooLow { solidity, min } => {
+                write!(f, "solidity {:.2} below min_solidity {:.2}", solidity, min)
+            }
+            RejectReason::ExtentTooLow { extent, min } => {
+                write!(f, "extent {:.2} below min_extent {:.2}", extent, min)
+            }
+        }
+    }
+}
+
+/// 一个被过滤掉的轮廓
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rejection {
+    pub rect: Rect,
+    pub reason: RejectReason,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "blob at ({}, {}, {}x{}): {}",
+            self.rect.x, self.rect.y, self.rect.width, self.rect.height, self.reason
+        )
+    }
+}
+
+/// 按 [`FilterConfig`] 里的规则逐条检查, 返回第一条不满足的
+pub struct BlobFilter {
+    config: FilterConfig,
+}
+
+impl BlobFilter {
+    pub fn new(config: &FilterConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    pub fn check(&self, blob: &Blob) -> Option<RejectReason> {
+        let config = &self.config;
+
+        // 宽和高都要够, 否则细长的一条也会被当成车
+        if blob.rect.width < config.min_w || blob.rect.height < config.min_h {
+            return Some(RejectReason::TooSmall {
+                width: blob.rect.width,
+                height: blob.rect.height,
+                min_w: config.min_w,
+                min_h: config.min_h,
+            });
+        }
+
+        if blob.area < config.min_area {
+            return Some(RejectReason::AreaTooSmall {
+                area: blob.area,
+                min: config.min_area,
+            });
+        }
+        if let Some(max) = config.max_area {
+            if blob.area > max {
+                return Some(RejectReason::AreaTooLarge {
+                    area: blob.area,
+                    max,
+                });
+            }
+        }
+
+        if !(config.min_aspect..=config.max_aspect).contains(&blob.aspect) {
+            return Some(RejectReason::AspectOutOfRange {
+                aspect: blob.aspect,
+                min: config.min_aspect,
+                max: config.max_aspect,
+            });
+        }
+
+        if blob.solidity < config.min_solidity {
+            return Some(RejectReason::SolidityTooLow {
+                solidity: blob.solidity,
+                min: config.min_solidity,
+            });
+        }
+
+        if blob.extent < config.min_extent {
+            return Some(RejectReason::ExtentTooLow {
+                extent: blob.extent,
+                min: config.min_extent,
+            });
+        }
+
+        None
+    }
+}
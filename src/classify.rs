@@ -0,0 +1,100 @@
+//! 按轮廓大小估计车型
+use serde::Serialize;
+
+use crate::blob::Blob;
+use crate::config::ClassConfig;
+use crate::counting::{Counts, Direction};
+
+/// 车型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VehicleClass {
+    Car,
+    Truck,
+    Motorbike,
+}
+
+impl VehicleClass {
+    /// 所有车型, 顺序就是 [`VehicleClass::index`]
+    pub const ALL: [VehicleClass; 3] = [
+        VehicleClass::Car,
+        VehicleClass::Truck,
+        VehicleClass::Motorbike,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VehicleClass::Car => "car",
+            VehicleClass::Truck => "truck",
+            VehicleClass::Motorbike => "motorbike",
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        match self {
+            VehicleClass::Car => 0,
+            VehicleClass::Truck => 1,
+            VehicleClass::Motorbike => 2,
+        }
+    }
+}
+
+/// 规则分类: 够大或者够长的是货车, 又小又窄的是摩托车, 其他都是小汽车
+pub struct VehicleClassifier {
+    config: ClassConfig,
+}
+
+impl VehicleClassifier {
+    pub fn new(config: &ClassConfig) -> Self {
+        Self {
+            config: config.clone(),
+        }
+    }
+
+    pub fn classify(&self, blob: &Blob) -> VehicleClass {
+        let config = &self.config;
+
+        if blob.area >= config.truck_min_area || blob.length >= config.truck_min_length {
+            return VehicleClass::Truck;
+        }
+
+        if blob.area <= config.motorbike_max_area && blob.width <= config.motorbike_max_width {
+            return VehicleClass::Motorbike;
+        }
+
+        VehicleClass::Car
+    }
+}
+
+/// 分车型, 分方向的累计数量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ClassCounts {
+    pub car: Counts,
+    pub truck: Counts,
+    pub motorbike: Counts,
+}
+
+impl ClassCounts {
+    pub fn get(&self, class: VehicleClass) -> Counts {
+        match class {
+            VehicleClass::Car => self.car,
+            VehicleClass::Truck => self.truck,
+            VehicleClass::Motorbike => self.motorbike,
+        }
+    }
+
+    pub fn add(&mut self, class: VehicleClass, direction: Direction) {
+        match class {
+            VehicleClass::Car => self.car.add(direction),
+            VehicleClass::Truck => self.truck.add(direction),
+            VehicleClass::Motorbike => self.motorbike.add(direction),
+        }
+    }
+
+    /// 加上另一份数量
+    pub fn merge(&mut self, other: ClassCounts) {
+        self.car.merge(other.car);
+        self.truck.merge(other.truck);
+        self.motorbike.merge(other.motorbike);
+    }
+}
@@ -0,0 +1,135 @@
+//! 各个演示程序共用的命令行参数
+use clap::Args;
+use opencv::prelude::*;
+
+use crate::output::{FrameSink, ImageSink, WindowSink};
+
+/// 单张图片输入
+#[derive(Debug, Clone, Args)]
+pub struct ImageArgs {
+    /// 输入图片路径
+    #[arg(short, long)]
+    pub input: String,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+impl ImageArgs {
+    /// 输出结果, 见 [`OutputArgs::show`]
+    pub fn show(&self, window: &str, mat: &Mat) -> opencv::Result<()> {
+        self.output.show(window, mat)
+    }
+
+    /// 显示中间结果, 见 [`OutputArgs::preview`]
+    pub fn preview(&self, window: &str, mat: &Mat) -> opencv::Result<()> {
+        self.output.preview(window, mat)
+    }
+}
+
+/// 两张图片输入, 用于特征匹配
+#[derive(Debug, Clone, Args)]
+pub struct PairArgs {
+    /// 要查找的图片
+    #[arg(long)]
+    pub src: String,
+
+    /// 被查找的图片
+    #[arg(long)]
+    pub dst: String,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+impl PairArgs {
+    /// 输出结果, 见 [`OutputArgs::show`]
+    pub fn show(&self, window: &str, mat: &Mat) -> opencv::Result<()> {
+        self.output.show(window, mat)
+    }
+}
+
+/// 结果输出到哪里
+///
+/// 传了 `--output` 就写文件, 不弹窗口(适合没有图形界面的服务器);
+/// 没有任何文件输出或者传了 `--display` 才用 `imshow` 显示, 传了 `--no-display` 就一定不显示
+#[derive(Debug, Clone, Args)]
+pub struct OutputArgs {
+    /// 结果保存路径
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// 即使传了 --output 也弹窗口显示
+    #[arg(long, conflicts_with = "no_display")]
+    pub display: bool,
+
+    /// 不弹窗口, 没有图形界面时用
+    #[arg(long)]
+    pub no_display: bool,
+}
+
+impl OutputArgs {
+    /// 是否需要弹窗口
+    pub fn displaying(&self) -> bool {
+        self.displaying_with(false)
+    }
+
+    /// 是否需要弹窗口, `other_files` 表示除了 `--output` 还有别的文件输出(比如逐帧结果)
+    pub fn displaying_with(&self, other_files: bool) -> bool {
+        self.display || (!self.no_display && self.output.is_none() && !other_files)
+    }
+
+    /// 输出最终结果: 保存到 `--output`, 需要的话再显示10秒
+    pub fn show(&self, window: &str, mat: &Mat) -> opencv::Result<()> {
+        if let Some(path) = &self.output {
+            ImageSink::new(path.as_str()).write(mat)?;
+        }
+
+        self.preview(window, mat)
+    }
+
+    /// 显示中间结果(比如原图), 只在需要弹窗口时显示, 不保存
+    pub fn preview(&self, window: &str, mat: &Mat) -> opencv::Result<()> {
+        if self.displaying() {
+            WindowSink::new(window, 10000).write(mat)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// `--fps` 的取值, 必须是正数
+pub fn parse_fps(value: &str) -> Result<f64, String> {
+    let fps: f64 = value
+        .parse()
+        .map_err(|_| format!("{} is not a number", value))?;
+    if !fps.is_finite() || fps <= 0f64 {
+        return Err(format!("fps must be a positive number, got {}", value));
+    }
+
+    Ok(fps)
+}
+
+/// `--stats-interval` 的取值, 分钟, 必须是正数
+pub fn parse_interval(value: &str) -> Result<f64, String> {
+    let minutes: f64 = value
+        .parse()
+        .map_err(|_| format!("{} is not a number", value))?;
+    if !minutes.is_finite() || minutes <= 0f64 {
+        return Err(format!("interval must be a positive number, got {}", value));
+    }
+
+    Ok(minutes)
+}
+
+/// `--iou` 的取值, 必须在 (0, 1] 里
+pub fn parse_iou(value: &str) -> Result<f64, String> {
+    let iou: f64 = value
+        .parse()
+        .map_err(|_| format!("{} is not a number", value))?;
+    if !(0f64 < iou && iou <= 1f64) {
+        return Err(format!("iou must be in (0, 1], got {}", value));
+    }
+
+    Ok(iou)
+}
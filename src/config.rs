@@ -0,0 +1,1221 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use opencv::core::Point;
+use opencv::imgproc::{MORPH_CROSS, MORPH_ELLIPSE, MORPH_RECT};
+use serde::{Deserialize, Serialize};
+
+use crate::classify::VehicleClass;
+use crate::counting::{CountLine, CountZone, Direction, Lane};
+use crate::geometry::has_collinear_triple;
+
+/// 配置文件读写或者校验失败
+#[derive(Debug)]
+pub enum ConfigError {
+    /// 文件读写失败
+    Io(PathBuf, std::io::Error),
+    /// 文件格式不对
+    Parse(PathBuf, String),
+    /// 某个参数的值不合法
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "cannot access {}: {}", path.display(), err),
+            ConfigError::Parse(path, err) => write!(f, "cannot parse {}: {}", path.display(), err),
+            ConfigError::Invalid(err) => write!(f, "invalid config: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+fn invalid<T>(message: String) -> Result<T, ConfigError> {
+    Err(ConfigError::Invalid(message))
+}
+
+/// 整条检测链路的配置, 可以从 TOML 或 JSON 文件读取, 没写的参数用默认值
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PipelineConfig {
+    pub detector: DetectorConfig,
+    pub blur: BlurConfig,
+    pub background: BackgroundConfig,
+    pub shadow: ShadowConfig,
+    pub morphology: MorphologyConfig,
+    pub filter: FilterConfig,
+    pub classes: ClassConfig,
+    pub tracker: TrackerConfig,
+    pub lines: Vec<LineConfig>,
+    pub zones: Vec<ZoneConfig>,
+    pub lanes: Vec<LaneConfig>,
+    /// 不写就处理整个画面
+    pub roi: Option<RoiConfig>,
+    /// 不写就不测速
+    pub calibration: Option<CalibrationConfig>,
+    pub incidents: IncidentConfig,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            detector: DetectorConfig::default(),
+            blur: BlurConfig::default(),
+            background: BackgroundConfig::default(),
+            shadow: ShadowConfig::default(),
+            morphology: MorphologyConfig::default(),
+            filter: FilterConfig::default(),
+            classes: ClassConfig::default(),
+            tracker: TrackerConfig::default(),
+            lines: vec![LineConfig {
+                name: "line".to_string(),
+                points: vec![[10, 550], [1200, 550]],
+                allowed: None,
+            }],
+            zones: Vec::new(),
+            lanes: Vec::new(),
+            roi: None,
+            calibration: None,
+            incidents: IncidentConfig::default(),
+        }
+    }
+}
+
+/// 检测器后端
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectorBackend {
+    /// 去背景找运动的物体, 用下面 `blur` 到 `classes` 的配置
+    Motion,
+    /// 用 YOLO 模型检测, 用 `detector.dnn` 的配置
+    Dnn,
+}
+
+/// 检测器, 见 [`crate::detector::Detector`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DetectorConfig {
+    pub backend: DetectorBackend,
+    pub dnn: DnnConfig,
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        Self {
+            backend: DetectorBackend::Motion,
+            dnn: DnnConfig::default(),
+        }
+    }
+}
+
+/// YOLO 模型, 见 [`crate::dnn::DnnDetector`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DnnConfig {
+    /// `.onnx` 或者 Darknet 的 `.weights`
+    pub model: String,
+    /// Darknet 的 `.cfg`, ONNX 模型不用
+    pub cfg: Option<String>,
+    /// 类别名文件, 每行一个, 不写就按 COCO 的类别顺序
+    pub labels: Option<String>,
+    /// 模型的输入尺寸
+    pub input_width: i32,
+    pub input_height: i32,
+    /// 置信度低于这个值的框丢掉
+    pub confidence: f32,
+    /// 非极大值抑制的交并比阈值
+    pub nms_threshold: f32,
+    /// 输出的坐标是相对整张图的比例(Darknet 模型), 否则是输入尺寸下的像素(常见的 ONNX 导出)
+    pub normalized: bool,
+    /// 哪些类别名算小汽车, 货车, 摩托车, 其他类别忽略
+    pub car: Vec<String>,
+    pub truck: Vec<String>,
+    pub motorbike: Vec<String>,
+}
+
+impl Default for DnnConfig {
+    fn default() -> Self {
+        Self {
+            model: String::new(),
+            cfg: None,
+            labels: None,
+            input_width: 416,
+            input_height: 416,
+            confidence: 0.5,
+            nms_threshold: 0.4,
+            normalized: false,
+            car: vec!["car".to_string()],
+            truck: vec!["truck".to_string(), "bus".to_string()],
+            motorbike: vec!["motorbike".to_string(), "motorcycle".to_string()],
+        }
+    }
+}
+
+impl DnnConfig {
+    /// 模型的类别名对应哪种车型, 不是车返回 `None`
+    pub fn vehicle_class(&self, label: &str) -> Option<VehicleClass> {
+        let label = label.to_string();
+        if self.car.contains(&label) {
+            Some(VehicleClass::Car)
+        } else if self.truck.contains(&label) {
+            Some(VehicleClass::Truck)
+        } else if self.motorbike.contains(&label) {
+            Some(VehicleClass::Motorbike)
+        } else {
+            None
+        }
+    }
+}
+
+/// 高斯去噪
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlurConfig {
+    /// 卷积核边长, 必须是正奇数
+    pub size: i32,
+    pub sigma: f64,
+}
+
+impl Default for BlurConfig {
+    fn default() -> Self {
+        Self {
+            size: 3,
+            sigma: 5f64,
+        }
+    }
+}
+
+/// 背景建模算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackgroundKind {
+    /// bgsegm 里的 MOG
+    Mog,
+    /// video 里的 MOG2, 可以检测阴影
+    Mog2,
+    /// video 里的 KNN, 可以检测阴影
+    Knn,
+    /// bgsegm 里的 GMG, 前几帧用来初始化, 没有输出
+    Gmg,
+    /// bgsegm 里的 CNT, 速度最快
+    Cnt,
+}
+
+/// 去背景, `model` 选算法, 每种算法的参数在各自的小节里
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackgroundConfig {
+    pub model: BackgroundKind,
+    /// 负数表示由算法自己决定
+    pub learning_rate: f64,
+    pub mog: MogConfig,
+    pub mog2: Mog2Config,
+    pub knn: KnnConfig,
+    pub gmg: GmgConfig,
+    pub cnt: CntConfig,
+}
+
+impl Default for BackgroundConfig {
+    fn default() -> Self {
+        Self {
+            model: BackgroundKind::Mog,
+            learning_rate: -1f64,
+            mog: MogConfig::default(),
+            mog2: Mog2Config::default(),
+            knn: KnnConfig::default(),
+            gmg: GmgConfig::default(),
+            cnt: CntConfig::default(),
+        }
+    }
+}
+
+impl BackgroundConfig {
+    /// 选中的背景模型会不会在掩码里标出阴影
+    pub fn labels_shadows(&self) -> bool {
+        match self.model {
+            BackgroundKind::Mog2 => self.mog2.detect_shadows,
+            BackgroundKind::Knn => self.knn.detect_shadows,
+            _ => false,
+        }
+    }
+}
+
+/// MOG去背景
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MogConfig {
+    /// 用多少帧作为历史
+    pub history: i32,
+    /// 高斯混合的个数
+    pub mixtures: i32,
+    pub background_ratio: f64,
+    /// 0表示自动
+    pub noise_sigma: f64,
+}
+
+impl Default for MogConfig {
+    fn default() -> Self {
+        Self {
+            history: 200,
+            mixtures: 5,
+            background_ratio: 0.7,
+            noise_sigma: 0f64,
+        }
+    }
+}
+
+/// MOG2去背景
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Mog2Config {
+    pub history: i32,
+    /// 像素和模型的马氏距离平方超过这个值就是前景
+    pub var_threshold: f64,
+    /// 阴影标成127
+    pub detect_shadows: bool,
+}
+
+impl Default for Mog2Config {
+    fn default() -> Self {
+        Self {
+            history: 500,
+            var_threshold: 16f64,
+            detect_shadows: true,
+        }
+    }
+}
+
+/// KNN去背景
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KnnConfig {
+    pub history: i32,
+    /// 像素和样本距离平方超过这个值就是前景
+    pub dist2_threshold: f64,
+    /// 阴影标成127
+    pub detect_shadows: bool,
+}
+
+impl Default for KnnConfig {
+    fn default() -> Self {
+        Self {
+            history: 500,
+            dist2_threshold: 400f64,
+            detect_shadows: true,
+        }
+    }
+}
+
+/// GMG去背景
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GmgConfig {
+    /// 前多少帧用来初始化背景
+    pub initialization_frames: i32,
+    /// 前景概率超过这个值就是前景
+    pub decision_threshold: f64,
+}
+
+impl Default for GmgConfig {
+    fn default() -> Self {
+        Self {
+            initialization_frames: 120,
+            decision_threshold: 0.8,
+        }
+    }
+}
+
+/// CNT去背景
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CntConfig {
+    /// 像素保持多少帧不变才算背景
+    pub min_pixel_stability: i32,
+    pub use_history: bool,
+    pub max_pixel_stability: i32,
+    /// 多线程
+    pub is_parallel: bool,
+}
+
+impl Default for CntConfig {
+    fn default() -> Self {
+        Self {
+            min_pixel_stability: 15,
+            use_history: true,
+            max_pixel_stability: 15 * 60,
+            is_parallel: true,
+        }
+    }
+}
+
+/// 阴影检测方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShadowMethod {
+    /// 背景模型能标阴影就用 `labels`, 否则用 `hsv`
+    Auto,
+    /// 用 MOG2 / KNN 标出来的阴影, 需要打开 `detect_shadows`
+    Labels,
+    /// 和彩色背景比较亮度, 色调, 饱和度, 所有背景模型都能用
+    Hsv,
+}
+
+/// 去阴影, 在去背景和腐蚀之间
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShadowConfig {
+    pub enabled: bool,
+    pub method: ShadowMethod,
+    /// 亮度和背景的比值不低于这个值才算阴影, 再暗就当成车
+    pub threshold: f64,
+    /// 亮度和背景的比值不高于这个值才算阴影
+    pub max_ratio: f64,
+    /// 色调最多差多少, 范围 0..180
+    pub max_hue_diff: i32,
+    /// 饱和度最多差多少, 范围 0..255
+    pub max_saturation_diff: i32,
+    /// 彩色背景的更新速度
+    pub learning_rate: f64,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            method: ShadowMethod::Auto,
+            threshold: 0.4,
+            max_ratio: 0.95,
+            max_hue_diff: 20,
+            max_saturation_diff: 40,
+            learning_rate: 0.01,
+        }
+    }
+}
+
+/// 卷积核形状
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KernelShape {
+    Rect,
+    Cross,
+    Ellipse,
+}
+
+impl KernelShape {
+    /// 对应 `get_structuring_element_def` 的 shape 参数
+    pub fn as_morph(&self) -> i32 {
+        match self {
+            KernelShape::Rect => MORPH_RECT,
+            KernelShape::Cross => MORPH_CROSS,
+            KernelShape::Ellipse => MORPH_ELLIPSE,
+        }
+    }
+}
+
+/// 腐蚀 / 膨胀 / 闭运算
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MorphologyConfig {
+    /// 卷积核边长
+    pub kernel_size: i32,
+    pub kernel_shape: KernelShape,
+    /// 腐蚀次数
+    pub erode_iterations: i32,
+    /// 膨胀次数
+    pub dilate_iterations: i32,
+    /// 闭运算做几遍
+    pub close_passes: u32,
+}
+
+impl Default for MorphologyConfig {
+    fn default() -> Self {
+        Self {
+            kernel_size: 5,
+            kernel_shape: KernelShape::Rect,
+            erode_iterations: 1,
+            dilate_iterations: 3,
+            close_passes: 2,
+        }
+    }
+}
+
+/// 过滤不是车的物体, 规则见 [`crate::blob::BlobFilter`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+    /// 外接矩形的宽和高都不能小于这个值
+    pub min_w: i32,
+    pub min_h: i32,
+    /// 轮廓面积
+    pub min_area: f64,
+    /// 不写就不限制
+    pub max_area: Option<f64>,
+    /// 最小外接矩形的长边 / 短边, 至少是1
+    pub min_aspect: f64,
+    pub max_aspect: f64,
+    /// 轮廓面积 / 凸包面积
+    pub min_solidity: f64,
+    /// 轮廓面积 / 外接矩形面积
+    pub min_extent: f64,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            min_w: 40,
+            min_h: 40,
+            min_area: 2000f64,
+            max_area: None,
+            min_aspect: 1f64,
+            max_aspect: 4f64,
+            min_solidity: 0.5,
+            min_extent: 0.3,
+        }
+    }
+}
+
+/// 车型分类, 规则见 [`crate::classify::VehicleClassifier`], 长度都是最小外接矩形的边长
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClassConfig {
+    /// 面积达到这个值就是货车
+    pub truck_min_area: f64,
+    /// 长边达到这个值就是货车
+    pub truck_min_length: f64,
+    /// 面积不超过这个值, 并且短边不超过 `motorbike_max_width` 就是摩托车
+    pub motorbike_max_area: f64,
+    pub motorbike_max_width: f64,
+}
+
+impl Default for ClassConfig {
+    fn default() -> Self {
+        Self {
+            truck_min_area: 30000f64,
+            truck_min_length: 300f64,
+            motorbike_max_area: 5000f64,
+            motorbike_max_width: 60f64,
+        }
+    }
+}
+
+/// 跟踪器参数, 见 [`crate::tracker::Tracker`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrackerConfig {
+    pub iou_threshold: f64,
+    /// 像素每秒
+    pub max_speed: f64,
+    /// 秒
+    pub max_age: f64,
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        Self {
+            iou_threshold: 0.3,
+            max_speed: 2000f64,
+            max_age: 0.4,
+        }
+    }
+}
+
+/// 检测线, `points` 是折线顶点 `[x, y]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LineConfig {
+    pub name: String,
+    pub points: Vec<[i32; 2]>,
+    /// 只允许这个方向通过, 反方向过线报逆行, 不写就不检查
+    #[serde(default)]
+    pub allowed: Option<Direction>,
+}
+
+impl LineConfig {
+    pub fn to_line(&self) -> CountLine {
+        CountLine::new(self.name.clone(), to_points(&self.points))
+    }
+}
+
+/// 计数区域, `polygon` 是多边形顶点 `[x, y]`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ZoneConfig {
+    pub name: String,
+    pub polygon: Vec<[i32; 2]>,
+}
+
+impl ZoneConfig {
+    pub fn to_zone(&self) -> CountZone {
+        CountZone::new(self.name.clone(), to_points(&self.polygon))
+    }
+}
+
+/// 车道, `polygon` 是多边形顶点 `[x, y]`, 过线计数时按车道分开统计
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LaneConfig {
+    pub name: String,
+    pub polygon: Vec<[i32; 2]>,
+}
+
+impl LaneConfig {
+    pub fn to_lane(&self) -> Lane {
+        Lane::new(self.name.clone(), to_points(&self.polygon))
+    }
+}
+
+/// 感兴趣区域, 只在区域里面找车, 见 [`crate::roi::Roi`]
+///
+/// 多边形和掩码图片可以一起用, 取并集
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RoiConfig {
+    /// 多边形顶点 `[x, y]`, 可以有多个
+    #[serde(default)]
+    pub polygons: Vec<Vec<[i32; 2]>>,
+    /// 掩码图片, 不是黑色的地方保留, 大小和画面不一样时会缩放
+    #[serde(default)]
+    pub mask: Option<String>,
+    /// 只处理区域的外接矩形, 区域比画面小很多时能快不少
+    #[serde(default)]
+    pub crop: bool,
+}
+
+/// 测速标定, 见 [`crate::speed::SpeedEstimator`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CalibrationConfig {
+    /// 路面上四个点在图上的位置 `[x, y]`, 像素
+    pub image: Vec<[f64; 2]>,
+    /// 同样四个点在路面上的位置 `[x, y]`, 米
+    pub world: Vec<[f64; 2]>,
+    /// 限速, 公里每小时, 不写就不报超速
+    #[serde(default)]
+    pub speed_limit: Option<f64>,
+    /// 用最近多少秒的位移计算车速
+    #[serde(default = "default_speed_window")]
+    pub window: f64,
+}
+
+fn default_speed_window() -> f64 {
+    1f64
+}
+
+/// 规则事件, 见 [`crate::incident::IncidentDetector`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IncidentConfig {
+    /// 中心点离开停下时的位置不超过这么多像素, 就算一直停着
+    pub still_distance: f64,
+    /// 不写就不报停车
+    pub stopped: Option<StoppedConfig>,
+    /// 不写就不算排队长度
+    pub queue: Option<QueueConfig>,
+}
+
+impl Default for IncidentConfig {
+    fn default() -> Self {
+        Self {
+            still_distance: 10f64,
+            stopped: None,
+            queue: None,
+        }
+    }
+}
+
+/// 停车: 停着超过 `seconds` 秒
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StoppedConfig {
+    #[serde(default = "default_stopped_seconds")]
+    pub seconds: f64,
+    /// 只在这些计数区域里报, 写 `zones` 里的名字, 不写就整个画面都报
+    #[serde(default)]
+    pub zones: Vec<String>,
+}
+
+fn default_stopped_seconds() -> f64 {
+    10f64
+}
+
+/// 排队长度: 停止线后面停着的车排了多远
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueueConfig {
+    /// 停止线, `lines` 里的名字
+    pub line: String,
+    /// 停止线后面排队的那段路, `zones` 里的名字
+    pub zone: String,
+    /// 停着超过多少秒算在排队
+    #[serde(default = "default_queue_seconds")]
+    pub seconds: f64,
+    /// 有车排队时每隔多少秒报一次长度
+    #[serde(default = "default_queue_interval")]
+    pub interval: f64,
+}
+
+fn default_queue_seconds() -> f64 {
+    2f64
+}
+
+fn default_queue_interval() -> f64 {
+    10f64
+}
+
+pub fn to_points(points: &[[i32; 2]]) -> Vec<Point> {
+    points.iter().map(|[x, y]| Point::new(*x, *y)).collect()
+}
+
+impl PipelineConfig {
+    /// 按扩展名读取 `.toml` 或 `.json` 配置, 读完之后做校验
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text =
+            fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+
+        let config: Self = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&text)
+                .map_err(|err| ConfigError::Parse(path.to_path_buf(), err.to_string()))?,
+            _ => toml::from_str(&text)
+                .map_err(|err| ConfigError::Parse(path.to_path_buf(), err.to_string()))?,
+        };
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// 保存到文件, 扩展名是 `.json` 写 JSON, 其他写 TOML
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+
+        let text = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::to_string_pretty(self)
+                .map_err(|err| ConfigError::Parse(path.to_path_buf(), err.to_string()))?,
+            // 先转成表再输出, 点的坐标才会写在一行里
+            _ => toml::Table::try_from(self)
+                .map(|table| table.to_string())
+                .map_err(|err| ConfigError::Parse(path.to_path_buf(), err.to_string()))?,
+        };
+
+        fs::write(path, text).map_err(|err| ConfigError::Io(path.to_path_buf(), err))
+    }
+
+    /// 所有浮点参数和它们的名字, NaN 和无穷大过不了后面的范围检查, 要先挑出来
+    fn floats(&self) -> Vec<(String, f64)> {
+        let mut floats = vec![
+            (
+                "detector.dnn.confidence",
+                self.detector.dnn.confidence as f64,
+            ),
+            (
+                "detector.dnn.nms_threshold",
+                self.detector.dnn.nms_threshold as f64,
+            ),
+            ("blur.sigma", self.blur.sigma),
+            ("background.learning_rate", self.background.learning_rate),
+            (
+                "background.mog.background_ratio",
+                self.background.mog.background_ratio,
+            ),
+            (
+                "background.mog.noise_sigma",
+                self.background.mog.noise_sigma,
+            ),
+            (
+                "background.mog2.var_threshold",
+                self.background.mog2.var_threshold,
+            ),
+            (
+                "background.knn.dist2_threshold",
+                self.background.knn.dist2_threshold,
+            ),
+            (
+                "background.gmg.decision_threshold",
+                self.background.gmg.decision_threshold,
+            ),
+            ("shadow.threshold", self.shadow.threshold),
+            ("shadow.max_ratio", self.shadow.max_ratio),
+            ("shadow.learning_rate", self.shadow.learning_rate),
+            ("filter.min_area", self.filter.min_area),
+            ("filter.min_aspect", self.filter.min_aspect),
+            ("filter.max_aspect", self.filter.max_aspect),
+            ("filter.min_solidity", self.filter.min_solidity),
+            ("filter.min_extent", self.filter.min_extent),
+            ("classes.truck_min_area", self.classes.truck_min_area),
+            ("classes.truck_min_length", self.classes.truck_min_length),
+            (
+                "classes.motorbike_max_area",
+                self.classes.motorbike_max_area,
+            ),
+            (
+                "classes.motorbike_max_width",
+                self.classes.motorbike_max_width,
+            ),
+            ("tracker.iou_threshold", self.tracker.iou_threshold),
+            ("tracker.max_speed", self.tracker.max_speed),
+            ("tracker.max_age", self.tracker.max_age),
+            ("incidents.still_distance", self.incidents.still_distance),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect::<Vec<_>>();
+
+        if let Some(max_area) = self.filter.max_area {
+            floats.push(("filter.max_area".to_string(), max_area));
+        }
+        if let Some(calibration) = &self.calibration {
+            for (i, [x, y]) in calibration.image.iter().enumerate() {
+                floats.push((format!("calibration.image[{}]", i), *x));
+                floats.push((format!("calibration.image[{}]", i), *y));
+            }
+            for (i, [x, y]) in calibration.world.iter().enumerate() {
+                floats.push((format!("calibration.world[{}]", i), *x));
+                floats.push((format!("calibration.world[{}]", i), *y));
+            }
+            floats.push(("calibration.window".to_string(), calibration.window));
+            if let Some(limit) = calibration.speed_limit {
+                floats.push(("calibration.speed_limit".to_string(), limit));
+            }
+        }
+        if let Some(stopped) = &self.incidents.stopped {
+            floats.push(("incidents.stopped.seconds".to_string(), stopped.seconds));
+        }
+        if let Some(queue) = &self.incidents.queue {
+            floats.push(("incidents.queue.seconds".to_string(), queue.seconds));
+            floats.push(("incidents.queue.interval".to_string(), queue.interval));
+        }
+
+        floats
+    }
+
+    /// 检查参数是否合法, 错误信息里带上参数名和当前值
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Some((name, value)) = self
+            .floats()
+            .into_iter()
+            .find(|(_, value)| !value.is_finite())
+        {
+            return invalid(format!("{} must be a finite number, got {}", name, value));
+        }
+
+        let dnn = &self.detector.dnn;
+        if self.detector.backend == DetectorBackend::Dnn && dnn.model.is_empty() {
+            return invalid("detector.dnn.model is required for the dnn backend".to_string());
+        }
+        if dnn.input_width <= 0 || dnn.input_height <= 0 {
+            return invalid(format!(
+                "detector.dnn input size must be positive, got {}x{}",
+                dnn.input_width, dnn.input_height
+            ));
+        }
+        if !(0f32..=1f32).contains(&dnn.confidence) {
+            return invalid(format!(
+                "detector.dnn.confidence must be in [0, 1], got {}",
+                dnn.confidence
+            ));
+        }
+        if !(0f32..=1f32).contains(&dnn.nms_threshold) {
+            return invalid(format!(
+                "detector.dnn.nms_threshold must be in [0, 1], got {}",
+                dnn.nms_threshold
+            ));
+        }
+
+        if self.blur.size <= 0 || self.blur.size % 2 == 0 {
+            return invalid(format!(
+                "blur.size must be a positive odd number, got {}",
+                self.blur.size
+            ));
+        }
+        if self.blur.sigma < 0f64 {
+            return invalid(format!(
+                "blur.sigma must not be negative, got {}",
+                self.blur.sigma
+            ));
+        }
+
+        let mog = &self.background.mog;
+        if mog.history <= 0 {
+            return invalid(format!(
+                "background.mog.history must be positive, got {}",
+                mog.history
+            ));
+        }
+        if mog.mixtures <= 0 {
+            return invalid(format!(
+                "background.mog.mixtures must be positive, got {}",
+                mog.mixtures
+            ));
+        }
+        if !(0f64..=1f64).contains(&mog.background_ratio) {
+            return invalid(format!(
+                "background.mog.background_ratio must be in [0, 1], got {}",
+                mog.background_ratio
+            ));
+        }
+        if mog.noise_sigma < 0f64 {
+            return invalid(format!(
+                "background.mog.noise_sigma must not be negative, got {}",
+                mog.noise_sigma
+            ));
+        }
+
+        let mog2 = &self.background.mog2;
+        if mog2.history <= 0 {
+            return invalid(format!(
+                "background.mog2.history must be positive, got {}",
+                mog2.history
+            ));
+        }
+        if mog2.var_threshold <= 0f64 {
+            return invalid(format!(
+                "background.mog2.var_threshold must be positive, got {}",
+                mog2.var_threshold
+            ));
+        }
+
+        let knn = &self.background.knn;
+        if knn.history <= 0 {
+            return invalid(format!(
+                "background.knn.history must be positive, got {}",
+                knn.history
+            ));
+        }
+        if knn.dist2_threshold <= 0f64 {
+            return invalid(format!(
+                "background.knn.dist2_threshold must be positive, got {}",
+                knn.dist2_threshold
+            ));
+        }
+
+        let gmg = &self.background.gmg;
+        if gmg.initialization_frames < 0 {
+            return invalid(format!(
+                "background.gmg.initialization_frames must not be negative, got {}",
+                gmg.initialization_frames
+            ));
+        }
+        if !(0f64..=1f64).contains(&gmg.decision_threshold) {
+            return invalid(format!(
+                "background.gmg.decision_threshold must be in [0, 1], got {}",
+                gmg.decision_threshold
+            ));
+        }
+
+        let cnt = &self.background.cnt;
+        if cnt.min_pixel_stability <= 0 || cnt.max_pixel_stability < cnt.min_pixel_stability {
+            return invalid(format!(
+                "background.cnt needs 0 < min_pixel_stability <= max_pixel_stability, got {} and {}",
+                cnt.min_pixel_stability, cnt.max_pixel_stability
+            ));
+        }
+
+        let shadow = &self.shadow;
+        if shadow.method == ShadowMethod::Labels
+            && shadow.enabled
+            && !self.background.labels_shadows()
+        {
+            return invalid(format!(
+                "shadow.method \"labels\" needs background.model mog2 or knn with detect_shadows, got {:?}",
+                self.background.model
+            ));
+        }
+        if !(0f64 < shadow.threshold
+            && shadow.threshold <= shadow.max_ratio
+            && shadow.max_ratio <= 1f64)
+        {
+            return invalid(format!(
+                "shadow needs 0 < threshold <= max_ratio <= 1, got {} and {}",
+                shadow.threshold, shadow.max_ratio
+            ));
+        }
+        if shadow.max_hue_diff < 0 || shadow.max_saturation_diff < 0 {
+            return invalid(format!(
+                "shadow.max_hue_diff and shadow.max_saturation_diff must not be negative, got {} and {}",
+                shadow.max_hue_diff, shadow.max_saturation_diff
+            ));
+        }
+        if !(0f64..=1f64).contains(&shadow.learning_rate) {
+            return invalid(format!(
+                "shadow.learning_rate must be in [0, 1], got {}",
+                shadow.learning_rate
+            ));
+        }
+
+        if self.morphology.kernel_size <= 0 {
+            return invalid(format!(
+                "morphology.kernel_size must be positive, got {}",
+                self.morphology.kernel_size
+            ));
+        }
+        if self.morphology.erode_iterations < 0 {
+            return invalid(format!(
+                "morphology.erode_iterations must not be negative, got {}",
+                self.morphology.erode_iterations
+            ));
+        }
+        if self.morphology.dilate_iterations < 0 {
+            return invalid(format!(
+                "morphology.dilate_iterations must not be negative, got {}",
+                self.morphology.dilate_iterations
+            ));
+        }
+
+        let filter = &self.filter;
+        if filter.min_w < 0 || filter.min_h < 0 {
+            return invalid(format!(
+                "filter.min_w and filter.min_h must not be negative, got {}x{}",
+                filter.min_w, filter.min_h
+            ));
+        }
+        if filter.min_area < 0f64 || filter.max_area.is_some_and(|max| max < filter.min_area) {
+            return invalid(format!(
+                "filter needs 0 <= min_area <= max_area, got {} and {:?}",
+                filter.min_area, filter.max_area
+            ));
+        }
+        if filter.min_aspect < 1f64 || filter.max_aspect < filter.min_aspect {
+            return invalid(format!(
+                "filter needs 1 <= min_aspect <= max_aspect, got {} and {}",
+                filter.min_aspect, filter.max_aspect
+            ));
+        }
+        if !(0f64..=1f64).contains(&filter.min_solidity) {
+            return invalid(format!(
+                "filter.min_solidity must be in [0, 1], got {}",
+                filter.min_solidity
+            ));
+        }
+        if !(0f64..=1f64).contains(&filter.min_extent) {
+            return invalid(format!(
+                "filter.min_extent must be in [0, 1], got {}",
+                filter.min_extent
+            ));
+        }
+
+        let classes = &self.classes;
+        if classes.motorbike_max_area >= classes.truck_min_area {
+            return invalid(format!(
+                "classes.motorbike_max_area must be below classes.truck_min_area, got {} and {}",
+                classes.motorbike_max_area, classes.truck_min_area
+            ));
+        }
+        if classes.truck_min_length <= 0f64 || classes.motorbike_max_width <= 0f64 {
+            return invalid(format!(
+                "classes.truck_min_length and classes.motorbike_max_width must be positive, got {} and {}",
+                classes.truck_min_length, classes.motorbike_max_width
+            ));
+        }
+
+        if !(0f64 < self.tracker.iou_threshold && self.tracker.iou_threshold <= 1f64) {
+            return invalid(format!(
+                "tracker.iou_threshold must be in (0, 1], got {}",
+                self.tracker.iou_threshold
+            ));
+        }
+        if self.tracker.max_speed < 0f64 {
+            return invalid(format!(
+                "tracker.max_speed must not be negative, got {}",
+                self.tracker.max_speed
+            ));
+        }
+        if self.tracker.max_age < 0f64 {
+            return invalid(format!(
+                "tracker.max_age must not be negative, got {}",
+                self.tracker.max_age
+            ));
+        }
+
+        let mut names = HashSet::new();
+        for line in &self.lines {
+            if !names.insert(line.name.as_str()) {
+                return invalid(format!("duplicate line/zone/lane name {:?}", line.name));
+            }
+            if line.points.len() < 2 {
+                return invalid(format!(
+                    "line {:?} needs at least 2 points, got {}",
+                    line.name,
+                    line.points.len()
+                ));
+            }
+        }
+        for zone in &self.zones {
+            if !names.insert(zone.name.as_str()) {
+                return invalid(format!("duplicate line/zone/lane name {:?}", zone.name));
+            }
+            if zone.polygon.len() < 3 {
+                return invalid(format!(
+                    "zone {:?} needs at least 3 points, got {}",
+                    zone.name,
+                    zone.polygon.len()
+                ));
+            }
+        }
+
+        for lane in &self.lanes {
+            if !names.insert(lane.name.as_str()) {
+                return invalid(format!("duplicate line/zone/lane name {:?}", lane.name));
+            }
+            if lane.polygon.len() < 3 {
+                return invalid(format!(
+                    "lane {:?} needs at least 3 points, got {}",
+                    lane.name,
+                    lane.polygon.len()
+                ));
+            }
+        }
+
+        if let Some(roi) = &self.roi {
+            if roi.polygons.is_empty() && roi.mask.is_none() {
+                return invalid("roi needs at least one polygon or a mask".to_string());
+            }
+            if let Some(polygon) = roi.polygons.iter().find(|polygon| polygon.len() < 3) {
+                return invalid(format!(
+                    "roi polygons need at least 3 points, got {}",
+                    polygon.len()
+                ));
+            }
+        }
+
+        if let Some(calibration) = &self.calibration {
+            if calibration.image.len() != 4 || calibration.world.len() != 4 {
+                return invalid(format!(
+                    "calibration needs exactly 4 image and 4 world points, got {} and {}",
+                    calibration.image.len(),
+                    calibration.world.len()
+                ));
+            }
+            if has_collinear_triple(&calibration.image) || has_collinear_triple(&calibration.world)
+            {
+                return invalid(
+                    "calibration points must not be repeated or have three on one line".to_string(),
+                );
+            }
+            if calibration.window <= 0f64 {
+                return invalid(format!(
+                    "calibration.window must be positive, got {}",
+                    calibration.window
+                ));
+            }
+            if let Some(limit) = calibration.speed_limit.filter(|limit| *limit <= 0f64) {
+                return invalid(format!(
+                    "calibration.speed_limit must be positive, got {}",
+                    limit
+                ));
+            }
+        }
+
+        let incidents = &self.incidents;
+        if incidents.still_distance < 0f64 {
+            return invalid(format!(
+                "incidents.still_distance must not be negative, got {}",
+                incidents.still_distance
+            ));
+        }
+        let zone_exists = |name: &str| self.zones.iter().any(|zone| zone.name == name);
+        if let Some(stopped) = &incidents.stopped {
+            if stopped.seconds <= 0f64 {
+                return invalid(format!(
+                    "incidents.stopped.seconds must be positive, got {}",
+                    stopped.seconds
+                ));
+            }
+            if let Some(zone) = stopped.zones.iter().find(|zone| !zone_exists(zone)) {
+                return invalid(format!("incidents.stopped.zones: unknown zone {:?}", zone));
+            }
+        }
+        if let Some(queue) = &incidents.queue {
+            if !self.lines.iter().any(|line| line.name == queue.line) {
+                return invalid(format!(
+                    "incidents.queue.line: unknown line {:?}",
+                    queue.line
+                ));
+            }
+            if !zone_exists(&queue.zone) {
+                return invalid(format!(
+                    "incidents.queue.zone: unknown zone {:?}",
+                    queue.zone
+                ));
+            }
+            if queue.seconds <= 0f64 || queue.interval <= 0f64 {
+                return invalid(format!(
+                    "incidents.queue.seconds and incidents.queue.interval must be positive, got {} and {}",
+                    queue.seconds, queue.interval
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_valid() {
+        PipelineConfig::default().validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_nan_and_infinity() {
+        let mut config = PipelineConfig::default();
+        config.tracker.max_speed = f64::NAN;
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("tracker.max_speed"), "{}", err);
+
+        let mut config = PipelineConfig::default();
+        config.filter.max_area = Some(f64::INFINITY);
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("filter.max_area"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_degenerate_calibration() {
+        let mut config = PipelineConfig {
+            calibration: Some(CalibrationConfig {
+                image: vec![
+                    [0f64, 0f64],
+                    [100f64, 0f64],
+                    [100f64, 100f64],
+                    [0f64, 100f64],
+                ],
+                world: vec![[0f64, 0f64], [3f64, 0f64], [3f64, 20f64], [0f64, 20f64]],
+                speed_limit: None,
+                window: 1f64,
+            }),
+            ..PipelineConfig::default()
+        };
+        config.validate().unwrap();
+
+        // 三个点在同一条线上
+        config.calibration.as_mut().unwrap().image[1] = [50f64, 50f64];
+        assert!(config.validate().is_err());
+
+        // 两个点重合
+        let calibration = config.calibration.as_mut().unwrap();
+        calibration.image[1] = [100f64, 0f64];
+        calibration.world[3] = [3f64, 20f64];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_zero_iou_threshold() {
+        let mut config = PipelineConfig::default();
+        config.tracker.iou_threshold = 0f64;
+        assert!(config.validate().is_err());
+    }
+}
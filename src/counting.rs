@@ -0,0 +1,426 @@
+use std::collections::HashSet;
+
+use opencv::core::Point;
+use serde::{Deserialize, Serialize};
+
+use crate::classify::VehicleClass;
+use crate::geometry::{point_in_polygon, segment_crossing};
+use crate::tracker::Track;
+
+/// 过线方向
+///
+/// 以检测线的画线方向为准: 从线的左侧穿到右侧是 `Down`, 反过来是 `Up`。
+/// 从左往右画的水平线, `Down` 就是从上往下
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// 从上往下(驶入)
+    Down,
+    /// 从下往上(驶出)
+    Up,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Down => "down",
+            Direction::Up => "up",
+        }
+    }
+}
+
+/// 一次计数, 过线或者进入区域
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountEvent {
+    /// 车辆的轨迹编号
+    pub track_id: u64,
+    /// 检测线或者区域的名字
+    pub zone: String,
+    /// 过线方向, 区域没有方向
+    pub direction: Option<Direction>,
+    /// 计数时估计的车型
+    pub class: VehicleClass,
+}
+
+/// 分方向的累计数量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Counts {
+    pub down: usize,
+    pub up: usize,
+}
+
+impl Counts {
+    pub fn total(&self) -> usize {
+        self.down + self.up
+    }
+
+    pub fn add(&mut self, direction: Direction) {
+        match direction {
+            Direction::Down => self.down += 1,
+            Direction::Up => self.up += 1,
+        }
+    }
+
+    /// 加上另一份数量
+    pub fn merge(&mut self, other: Counts) {
+        self.down += other.down;
+        self.up += other.up;
+    }
+}
+
+/// 检测线, 可以是任意角度的折线
+#[derive(Debug, Clone)]
+pub struct CountLine {
+    pub name: String,
+    /// 折线的顶点, 至少两个
+    pub points: Vec<Point>,
+    counts: Counts,
+    counted: HashSet<u64>,
+}
+
+impl CountLine {
+    pub fn new(name: impl Into<String>, points: Vec<Point>) -> Self {
+        Self {
+            name: name.into(),
+            points,
+            counts: Counts::default(),
+            counted: HashSet::new(),
+        }
+    }
+
+    /// 这条线上的累计数量
+    pub fn counts(&self) -> Counts {
+        self.counts
+    }
+
+    /// 轨迹最近一次移动是否穿过了这条线, 穿过的话返回方向
+    pub fn crossing(&self, track: &Track) -> Option<Direction> {
+        let from = track.previous_center()?;
+        let to = track.center();
+
+        self.points.windows(2).find_map(|segment| {
+            segment_crossing(segment[0], segment[1], from, to).map(|side| {
+                if side < 0 {
+                    Direction::Down
+                } else {
+                    Direction::Up
+                }
+            })
+        })
+    }
+
+    /// 每条轨迹在这条线上只计数一次
+    pub fn update(&mut self, track: &Track) -> Option<CountEvent> {
+        if self.counted.contains(&track.id) {
+            return None;
+        }
+
+        let direction = self.crossing(track)?;
+        self.counted.insert(track.id);
+        self.counts.add(direction);
+
+        Some(CountEvent {
+            track_id: track.id,
+            zone: self.name.clone(),
+            direction: Some(direction),
+            class: track.class(),
+        })
+    }
+}
+
+/// 多边形计数区域, 车辆中心点进入区域时计数
+#[derive(Debug, Clone)]
+pub struct CountZone {
+    pub name: String,
+    /// 多边形的顶点, 至少三个
+    pub polygon: Vec<Point>,
+    entered: usize,
+    counted: HashSet<u64>,
+    inside: HashSet<u64>,
+}
+
+impl CountZone {
+    pub fn new(name: impl Into<String>, polygon: Vec<Point>) -> Self {
+        Self {
+            name: name.into(),
+            polygon,
+            entered: 0,
+            counted: HashSet::new(),
+            inside: HashSet::new(),
+        }
+    }
+
+    /// 累计进入过区域的车辆数
+    pub fn entered(&self) -> usize {
+        self.entered
+    }
+
+    /// 当前在区域内的车辆数
+    pub fn inside(&self) -> usize {
+        self.inside.len()
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        point_in_polygon(point, &self.polygon)
+    }
+
+    /// 每条轨迹在这个区域只计数一次
+    pub fn update(&mut self, track: &Track) -> Option<CountEvent> {
+        if !self.contains(track.center()) {
+            self.inside.remove(&track.id);
+            return None;
+        }
+
+        self.inside.insert(track.id);
+
+        if !self.counted.insert(track.id) {
+            return None;
+        }
+        self.entered += 1;
+
+        Some(CountEvent {
+            track_id: track.id,
+            zone: self.name.clone(),
+            direction: None,
+            class: track.class(),
+        })
+    }
+
+    /// 丢掉已经消失的轨迹
+    pub fn retain(&mut self, alive: &HashSet<u64>) {
+        self.inside.retain(|id| alive.contains(id));
+    }
+}
+
+/// 车道, 车辆过线计数时再按所在车道分开统计
+#[derive(Debug, Clone)]
+pub struct Lane {
+    pub name: String,
+    /// 多边形的顶点, 至少三个
+    pub polygon: Vec<Point>,
+    counts: Counts,
+}
+
+impl Lane {
+    pub fn new(name: impl Into<String>, polygon: Vec<Point>) -> Self {
+        Self {
+            name: name.into(),
+            polygon,
+            counts: Counts::default(),
+        }
+    }
+
+    /// 累计的分方向数量
+    pub fn counts(&self) -> Counts {
+        self.counts
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        point_in_polygon(point, &self.polygon)
+    }
+
+    /// 车辆过线被计数时调用, 同一辆车只应该调用一次
+    pub fn count(&mut self, track: &Track, direction: Direction) -> CountEvent {
+        self.counts.add(direction);
+
+        CountEvent {
+            track_id: track.id,
+            zone: self.name.clone(),
+            direction: Some(direction),
+            class: track.class(),
+        }
+    }
+}
+
+/// 轨迹属于哪条车道: 历史中心点落在哪条车道里的次数最多, 一样多时看当前位置, 都不在车道里返回 `None`
+pub fn assign_lane(lanes: &[Lane], trail: &[Point]) -> Option<usize> {
+    let current = *trail.last()?;
+
+    lanes
+        .iter()
+        .enumerate()
+        .map(|(index, lane)| {
+            let votes = trail.iter().filter(|point| lane.contains(**point)).count();
+            (index, votes, lane.contains(current))
+        })
+        .filter(|(_, votes, _)| *votes > 0)
+        .max_by_key(|(_, votes, current)| (*votes, *current))
+        .map(|(index, _, _)| index)
+}
+
+/// 某条检测线的累计数量
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineCount {
+    pub name: String,
+    pub counts: Counts,
+}
+
+/// 某个区域的累计数量
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZoneCount {
+    pub name: String,
+    pub entered: usize,
+    pub inside: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::Detection;
+    use crate::tracker::Tracker;
+    use opencv::core::Rect;
+
+    /// 编号是 `id`, 依次经过 `trail` 上各点的轨迹
+    fn track(id: u64, trail: &[Point]) -> Track {
+        let mut tracker = Tracker::default();
+        let first = trail[0];
+        tracker.update(
+            &[Detection {
+                rect: Rect::new(first.x - 5, first.y - 5, 10, 10),
+                center: first,
+                class: VehicleClass::Car,
+            }],
+            0f64,
+        );
+
+        let mut track = tracker.tracks()[0].clone();
+        track.id = id;
+        track.trail = trail.to_vec();
+        track
+    }
+
+    fn line() -> CountLine {
+        CountLine::new("line", vec![Point::new(0, 100), Point::new(200, 100)])
+    }
+
+    /// 一帧一帧地移动轨迹, 返回每一帧的计数方向
+    fn run(line: &mut CountLine, id: u64, points: &[Point]) -> Vec<Option<Direction>> {
+        (1..points.len())
+            .map(|end| {
+                line.update(&track(id, &points[..=end]))
+                    .and_then(|event| event.direction)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn crossing_in_each_direction() {
+        let mut line = line();
+        let down = run(&mut line, 1, &[Point::new(50, 80), Point::new(50, 120)]);
+        let up = run(&mut line, 2, &[Point::new(60, 120), Point::new(60, 80)]);
+
+        assert_eq!(down, [Some(Direction::Down)]);
+        assert_eq!(up, [Some(Direction::Up)]);
+        assert_eq!(line.counts(), Counts { down: 1, up: 1 });
+    }
+
+    #[test]
+    fn stopping_on_the_line_counts_once() {
+        let mut line = line();
+        let counted = run(
+            &mut line,
+            1,
+            &[
+                Point::new(50, 90),
+                Point::new(50, 100),
+                Point::new(50, 100),
+                Point::new(50, 110),
+            ],
+        );
+
+        assert_eq!(counted, [Some(Direction::Down), None, None]);
+        assert_eq!(line.counts().total(), 1);
+    }
+
+    #[test]
+    fn crossing_back_is_not_counted_again() {
+        let mut line = line();
+        let counted = run(
+            &mut line,
+            1,
+            &[Point::new(50, 90), Point::new(50, 110), Point::new(50, 90)],
+        );
+
+        assert_eq!(counted, [Some(Direction::Down), None]);
+    }
+
+    #[test]
+    fn touching_the_end_of_the_line() {
+        let mut line = line();
+        let through_end = run(&mut line, 1, &[Point::new(200, 90), Point::new(200, 110)]);
+        let past_end = run(&mut line, 2, &[Point::new(210, 90), Point::new(210, 110)]);
+
+        assert_eq!(through_end, [Some(Direction::Down)]);
+        assert_eq!(past_end, [None]);
+    }
+
+    #[test]
+    fn polyline_counts_on_any_segment() {
+        let mut line = CountLine::new(
+            "bend",
+            vec![Point::new(0, 100), Point::new(100, 100), Point::new(200, 0)],
+        );
+        // 穿过第二段斜线, 起点在画线方向的左侧
+        let counted = run(&mut line, 1, &[Point::new(130, 40), Point::new(180, 90)]);
+
+        assert_eq!(counted, [Some(Direction::Down)]);
+    }
+
+    #[test]
+    fn zone_counts_entries_once() {
+        let mut zone = CountZone::new(
+            "zone",
+            vec![
+                Point::new(0, 0),
+                Point::new(100, 0),
+                Point::new(100, 100),
+                Point::new(0, 100),
+            ],
+        );
+
+        assert!(zone.update(&track(1, &[Point::new(150, 50)])).is_none());
+        assert!(zone.update(&track(1, &[Point::new(50, 50)])).is_some());
+        assert_eq!(zone.inside(), 1);
+        assert!(zone.update(&track(1, &[Point::new(150, 50)])).is_none());
+        assert!(zone.update(&track(1, &[Point::new(50, 50)])).is_none());
+
+        assert_eq!(zone.entered(), 1);
+        assert_eq!(zone.inside(), 1);
+    }
+
+    fn lanes() -> Vec<Lane> {
+        let square = |x: i32| {
+            vec![
+                Point::new(x, 0),
+                Point::new(x + 100, 0),
+                Point::new(x + 100, 100),
+                Point::new(x, 100),
+            ]
+        };
+        vec![
+            Lane::new("left", square(0)),
+            Lane::new("right", square(100)),
+        ]
+    }
+
+    #[test]
+    fn lane_by_majority_of_the_trail() {
+        let trail = [Point::new(50, 10), Point::new(50, 20), Point::new(150, 30)];
+        assert_eq!(assign_lane(&lanes(), &trail), Some(0));
+    }
+
+    #[test]
+    fn lane_vote_tie_goes_to_the_current_position() {
+        let trail = [Point::new(50, 10), Point::new(150, 20)];
+        assert_eq!(assign_lane(&lanes(), &trail), Some(1));
+
+        let trail = [Point::new(150, 10), Point::new(50, 20)];
+        assert_eq!(assign_lane(&lanes(), &trail), Some(0));
+    }
+
+    #[test]
+    fn no_lane_outside_all_lanes() {
+        assert_eq!(assign_lane(&lanes(), &[Point::new(500, 50)]), None);
+        assert_eq!(assign_lane(&lanes(), &[]), None);
+    }
+}
@@ -0,0 +1,207 @@
+//! 检测器: 从一帧画面里找出车辆, 后面的跟踪和计数不关心用的是哪一种
+use opencv::core::{bitwise_and, no_array, Point, Scalar, Size, BORDER_CONSTANT, BORDER_DEFAULT};
+use opencv::imgproc::{
+    cvt_color_def, dilate, erode, find_contours, gaussian_blur_def, get_structuring_element_def,
+    morphology_default_border_value, morphology_ex_def, CHAIN_APPROX_SIMPLE, COLOR_BGR2GRAY,
+    MORPH_CLOSE, RETR_TREE,
+};
+use opencv::prelude::*;
+use opencv::types::VectorOfVectorOfPoint;
+
+use crate::background::{create_background_model, BackgroundModel};
+use crate::blob::{Blob, BlobFilter, Rejection};
+use crate::classify::VehicleClassifier;
+use crate::config::{DetectorBackend, PipelineConfig};
+use crate::dnn::DnnDetector;
+use crate::pipeline::{center, Detection};
+use crate::roi::Roi;
+use crate::shadow::ShadowRemover;
+
+/// 一帧的检测结果
+#[derive(Debug, Clone, Default)]
+pub struct DetectorOutput {
+    /// 认为是车的目标
+    pub detections: Vec<Detection>,
+    /// 被过滤掉的轮廓和原因, 只有按轮廓检测的后端才有
+    pub rejected: Vec<Rejection>,
+}
+
+/// 车辆检测器
+pub trait Detector {
+    /// 检测一帧(BGR), 不会修改原图
+    fn detect(&mut self, frame: &Mat) -> opencv::Result<DetectorOutput>;
+}
+
+/// 按配置里的 `detector.backend` 创建检测器
+pub fn create_detector(config: &PipelineConfig) -> opencv::Result<Box<dyn Detector>> {
+    let detector: Box<dyn Detector> = match config.detector.backend {
+        DetectorBackend::Motion => Box::new(MotionDetector::from_config(config)?),
+        DetectorBackend::Dnn => Box::new(DnnDetector::new(&config.detector.dnn)?),
+    };
+
+    Ok(detector)
+}
+
+/// 运动检测, 画面里动的东西就是车
+///
+/// 灰度 -> 高斯去噪 -> 去背景 -> 去阴影(可选) -> 腐蚀/膨胀/闭运算 -> 感兴趣区域(可选) -> 查找轮廓 -> 过滤 -> 估计车型
+pub struct MotionDetector {
+    config: PipelineConfig,
+    background: Box<dyn BackgroundModel>,
+    shadow: Option<ShadowRemover>,
+    roi: Option<Roi>,
+    kernel: Mat,
+    filter: BlobFilter,
+    classifier: VehicleClassifier,
+}
+
+impl MotionDetector {
+    pub fn from_config(config: &PipelineConfig) -> opencv::Result<Self> {
+        // 去后背景, 默认用MOG, 参数history:200, 如果视频是25帧, 大概取8帧图片作为历史, 只有像素点发生移动才会认为是前背景
+        let background = create_background_model(&config.background)?;
+
+        let shadow = config
+            .shadow
+            .enabled
+            .then(|| ShadowRemover::new(&config.shadow, &config.background));
+
+        let roi = config.roi.as_ref().map(Roi::new).transpose()?;
+
+        let morphology = &config.morphology;
+        let kernel = get_structuring_element_def(
+            morphology.kernel_shape.as_morph(),
+            Size::new(morphology.kernel_size, morphology.kernel_size),
+        )?;
+
+        Ok(Self {
+            config: config.clone(),
+            background,
+            shadow,
+            roi,
+            kernel,
+            filter: BlobFilter::new(&config.filter),
+            classifier: VehicleClassifier::new(&config.classes),
+        })
+    }
+}
+
+impl Detector for MotionDetector {
+    fn detect(&mut self, frame: &Mat) -> opencv::Result<DetectorOutput> {
+        // 只处理感兴趣区域的外接矩形, 找到的轮廓再平移回整个画面的坐标
+        let mut offset = Point::new(0, 0);
+        let cropped;
+        let frame = match &mut self.roi {
+            Some(roi) => {
+                roi.update(frame.size()?)?;
+                match roi.crop() {
+                    Some(rect) => {
+                        offset = Point::new(rect.x, rect.y);
+                        cropped = Mat::roi(frame, rect)?.try_clone()?;
+                        &cropped
+                    }
+                    None => frame,
+                }
+            }
+            None => frame,
+        };
+
+        // 转灰度
+        let mut cvt_frame = Mat::default();
+        cvt_color_def(frame, &mut cvt_frame, COLOR_BGR2GRAY)?;
+
+        // 去噪
+        let blur_config = &self.config.blur;
+        let mut blur = Mat::default();
+        gaussian_blur_def(
+            &cvt_frame,
+            &mut blur,
+            Size::new(blur_config.size, blur_config.size),
+            blur_config.sigma,
+        )?;
+
+        // 去背景
+        let mut mask = Mat::default();
+        self.background
+            .segment(&blur, &mut mask, self.config.background.learning_rate)?;
+
+        // 去阴影, 免得相邻的车被影子连成一块
+        if let Some(shadow) = &mut self.shadow {
+            shadow.apply(frame, &mut mask)?;
+        }
+
+        let morphology = &self.config.morphology;
+
+        // 腐蚀(在去掉一些小噪点)
+        let mut erode_mat = Mat::default();
+        erode(
+            &mask,
+            &mut erode_mat,
+            &self.kernel,
+            Point::new(-1, -1),
+            morphology.erode_iterations,
+            BORDER_CONSTANT,
+            morphology_default_border_value()?,
+        )?;
+
+        // 经过腐蚀, 噪点没有, 图片变小了, 在通过膨胀放大到原来
+        let mut dilate_mat = Mat::default();
+        dilate(
+            &erode_mat,
+            &mut dilate_mat,
+            &self.kernel,
+            Point::new(-1, -1),
+            morphology.dilate_iterations,
+            BORDER_CONSTANT,
+            Scalar::from(BORDER_DEFAULT),
+        )?;
+
+        // 闭运算,去掉物内部小块
+        let mut close_mat = dilate_mat;
+        for _ in 0..morphology.close_passes {
+            let mut next = Mat::default();
+            morphology_ex_def(&close_mat, &mut next, MORPH_CLOSE, &self.kernel)?;
+            close_mat = next;
+        }
+
+        // 区域外面的前景全部去掉
+        if let Some(roi) = &self.roi {
+            let mut masked = Mat::default();
+            bitwise_and(&close_mat, roi.mask(), &mut masked, &no_array())?;
+            close_mat = masked;
+        }
+
+        // 查找轮廓
+        let mut contours = VectorOfVectorOfPoint::new();
+        find_contours(
+            &close_mat,
+            &mut contours,
+            RETR_TREE,
+            CHAIN_APPROX_SIMPLE,
+            offset,
+        )?;
+
+        let mut output = DetectorOutput::default();
+
+        for list in contours.iter() {
+            let blob = Blob::measure(&list)?;
+            let rect = blob.rect;
+
+            // 过滤不是车的物体
+            if let Some(reason) = self.filter.check(&blob) {
+                output.rejected.push(Rejection { rect, reason });
+                continue;
+            }
+
+            // 获取车辆的中心点
+            let car_center_point = center(&rect);
+
+            output.detections.push(Detection {
+                rect,
+                center: car_center_point,
+                class: self.classifier.classify(&blob),
+            });
+        }
+
+        Ok(output)
+    }
+}
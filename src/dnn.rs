@@ -0,0 +1,196 @@
+//! 用 `opencv::dnn` 在 CPU 上跑 YOLO 模型检测车辆, 停着的车和镜头抖动都不影响
+use std::fs;
+
+use opencv::core::{Rect, Scalar, Size, StsError, CV_32F};
+use opencv::dnn::{
+    blob_from_image, nms_boxes_def, read_net, Net, DNN_BACKEND_OPENCV, DNN_TARGET_CPU,
+};
+use opencv::prelude::*;
+use opencv::types::{VectorOfMat, VectorOfRect, VectorOfString, VectorOff32, VectorOfi32};
+
+use crate::classify::VehicleClass;
+use crate::config::DnnConfig;
+use crate::detector::{Detector, DetectorOutput};
+use crate::pipeline::{center, Detection};
+
+/// 没有类别名文件时按 COCO 的类别顺序
+#[rustfmt::skip]
+const COCO_LABELS: [&str; 80] = [
+    "person", "bicycle", "car", "motorbike", "aeroplane", "bus", "train", "truck", "boat",
+    "traffic light", "fire hydrant", "stop sign", "parking meter", "bench", "bird", "cat", "dog",
+    "horse", "sheep", "cow", "elephant", "bear", "zebra", "giraffe", "backpack", "umbrella",
+    "handbag", "tie", "suitcase", "frisbee", "skis", "snowboard", "sports ball", "kite",
+    "baseball bat", "baseball glove", "skateboard", "surfboard", "tennis racket", "bottle",
+    "wine glass", "cup", "fork", "knife", "spoon", "bowl", "banana", "apple", "sandwich", "orange",
+    "broccoli", "carrot", "hot dog", "pizza", "donut", "cake", "chair", "sofa", "pottedplant",
+    "bed", "diningtable", "toilet", "tvmonitor", "laptop", "mouse", "remote", "keyboard",
+    "cell phone", "microwave", "oven", "toaster", "sink", "refrigerator", "book", "clock", "vase",
+    "scissors", "teddy bear", "hair drier", "toothbrush",
+];
+
+/// YOLO 检测器, 支持 ONNX 和 Darknet 模型
+///
+/// 输出的每一行是 `[cx, cy, w, h, objectness, 各类别得分...]`(YOLOv3 / v4 / v5 的格式),
+/// 坐标是相对整张图的比例(Darknet)还是输入尺寸下的像素(常见的 ONNX 导出)由 `normalized` 配置;
+/// YOLOv8 那种转置过, 没有 objectness 的输出不支持, 会返回错误
+pub struct DnnDetector {
+    config: DnnConfig,
+    net: Net,
+    out_names: VectorOfString,
+    /// 每个类别编号对应的车型, 不是车的类别是 `None`
+    classes: Vec<Option<VehicleClass>>,
+}
+
+impl DnnDetector {
+    pub fn new(config: &DnnConfig) -> opencv::Result<Self> {
+        // read_net 按扩展名识别 .onnx 和 .weights
+        let mut net = read_net(&config.model, config.cfg.as_deref().unwrap_or_default(), "")?;
+        if net.empty()? {
+            return Err(opencv::Error::new(
+                StsError,
+                format!("cannot load model {}", config.model),
+            ));
+        }
+        net.set_preferable_backend(DNN_BACKEND_OPENCV)?;
+        net.set_preferable_target(DNN_TARGET_CPU)?;
+
+        let out_names = net.get_unconnected_out_layers_names()?;
+
+        let labels: Vec<String> = match &config.labels {
+            Some(path) => fs::read_to_string(path)
+                .map_err(|err| {
+                    opencv::Error::new(StsError, format!("cannot read {}: {}", path, err))
+                })?
+                .lines()
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty())
+                .collect(),
+            None => COCO_LABELS.iter().map(|label| label.to_string()).collect(),
+        };
+
+        let classes = labels
+            .iter()
+            .map(|label| config.vehicle_class(label))
+            .collect();
+
+        Ok(Self {
+            config: config.clone(),
+            net,
+            out_names,
+            classes,
+        })
+    }
+}
+
+impl Detector for DnnDetector {
+    fn detect(&mut self, frame: &Mat) -> opencv::Result<DetectorOutput> {
+        let input = Size::new(self.config.input_width, self.config.input_height);
+        let blob = blob_from_image(
+            frame,
+            1f64 / 255f64,
+            input,
+            Scalar::default(),
+            true,
+            false,
+            CV_32F,
+        )?;
+        self.net.set_input_def(&blob)?;
+
+        let mut outputs = VectorOfMat::new();
+        self.net.forward(&mut outputs, &self.out_names)?;
+
+        // 坐标换算回原图
+        let (scale_x, scale_y) = if self.config.normalized {
+            (frame.cols() as f32, frame.rows() as f32)
+        } else {
+            (
+                frame.cols() as f32 / input.width as f32,
+                frame.rows() as f32 / input.height as f32,
+            )
+        };
+
+        let mut rects = VectorOfRect::new();
+        let mut scores = VectorOff32::new();
+        let mut classes = Vec::new();
+
+        // 每行是 4 个坐标, objectness 和每个类别的得分
+        let cols = 5 + self.classes.len();
+        for output in outputs.iter() {
+            let size = output.mat_size();
+            let dims: Vec<i32> = (0..size.len()).map(|i| size[i]).collect();
+            let layout_ok = match dims.as_slice() {
+                [_, last] => *last as usize == cols,
+                [1, _, last] => *last as usize == cols,
+                _ => false,
+            };
+            if !layout_ok {
+                return Err(opencv::Error::new(
+                    StsError,
+                    format!(
+                        "model output has shape {:?}, expected [N, {}] or [1, N, {}] with rows of \
+                         [cx, cy, w, h, objectness, {} class scores]",
+                        dims,
+                        cols,
+                        cols,
+                        self.classes.len()
+                    ),
+                ));
+            }
+
+            for row in output.data_typed::<f32>()?.chunks_exact(cols) {
+                // 得分最高的类别
+                let mut class_id = 0;
+                let mut class_score = 0f32;
+                for (id, score) in row[5..].iter().enumerate() {
+                    if *score > class_score {
+                        class_id = id;
+                        class_score = *score;
+                    }
+                }
+
+                let score = row[4] * class_score;
+                if score < self.config.confidence {
+                    continue;
+                }
+
+                // 只留下是车的类别
+                let Some(class) = self.classes.get(class_id).copied().flatten() else {
+                    continue;
+                };
+
+                let width = row[2] * scale_x;
+                let height = row[3] * scale_y;
+                rects.push(Rect::new(
+                    (row[0] * scale_x - width / 2f32) as i32,
+                    (row[1] * scale_y - height / 2f32) as i32,
+                    width as i32,
+                    height as i32,
+                ));
+                scores.push(score);
+                classes.push(class);
+            }
+        }
+
+        // 不分类别做非极大值抑制, 免得同一辆车既是 car 又是 truck
+        let mut keep = VectorOfi32::new();
+        nms_boxes_def(
+            &rects,
+            &scores,
+            self.config.confidence,
+            self.config.nms_threshold,
+            &mut keep,
+        )?;
+
+        let mut output = DetectorOutput::default();
+        for index in keep.iter() {
+            let rect = rects.get(index as usize)?;
+            output.detections.push(Detection {
+                rect,
+                center: center(&rect),
+                class: classes[index as usize],
+            });
+        }
+
+        Ok(output)
+    }
+}
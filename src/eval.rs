@@ -0,0 +1,683 @@
+//! 用标注数据评估检测, 跟踪和计数的准确率
+//!
+//! 标注支持两种格式:
+//! - JSON: `{"frames": [{"frame": 0, "objects": [{"id": 1, "x": 0, "y": 0, "width": 10, "height": 10}]}],
+//!   "events": [{"frame": 120, "direction": "down"}]}`, 帧号从0开始
+//! - MOT: 每行 `frame,id,x,y,width,height,...`, 帧号从1开始, 过线事件另外用 JSON 给
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use opencv::core::Rect;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ConfigError, PipelineConfig};
+use crate::counting::{Counts, Direction};
+use crate::pipeline::{FrameResult, VehicleCounter};
+use crate::source::{open_source, Frame};
+use crate::tracker::iou;
+
+/// 标注的一个目标
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroundTruthObject {
+    pub id: u64,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl GroundTruthObject {
+    pub fn rect(&self) -> Rect {
+        Rect::new(self.x, self.y, self.width, self.height)
+    }
+}
+
+/// 标注的一个过线事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GroundTruthEvent {
+    pub frame: u64,
+    pub direction: Direction,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GroundTruthFrame {
+    frame: u64,
+    #[serde(default)]
+    objects: Vec<GroundTruthObject>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GroundTruthFile {
+    #[serde(default)]
+    frames: Vec<GroundTruthFrame>,
+    #[serde(default)]
+    events: Vec<GroundTruthEvent>,
+}
+
+/// 标注数据, 只评估标注过的帧
+#[derive(Debug, Clone, Default)]
+pub struct GroundTruth {
+    /// 帧号(从0开始) -> 这一帧的所有目标, 没有目标的帧也在里面
+    pub frames: BTreeMap<u64, Vec<GroundTruthObject>>,
+    pub events: Vec<GroundTruthEvent>,
+}
+
+fn parse_error(path: &Path, message: impl ToString) -> ConfigError {
+    ConfigError::Parse(path.to_path_buf(), message.to_string())
+}
+
+impl GroundTruth {
+    /// `.json` 按 JSON 读, 其他按 MOT 读
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text =
+            fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => {
+                let file: GroundTruthFile =
+                    serde_json::from_str(&text).map_err(|err| parse_error(path, err))?;
+
+                let mut truth = Self {
+                    events: file.events,
+                    ..Self::default()
+                };
+                for frame in file.frames {
+                    truth
+                        .frames
+                        .entry(frame.frame)
+                        .or_default()
+                        .extend(frame.objects);
+                }
+
+                Ok(truth)
+            }
+            _ => Self::parse_mot(&text).map_err(|err| parse_error(path, err)),
+        }
+    }
+
+    /// MOT 格式没写的帧当作没有目标, 第一帧到最后一帧之间都参与评估
+    fn parse_mot(text: &str) -> Result<Self, String> {
+        let mut truth = Self::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            if fields.len() < 6 {
+                return Err(format!("line {}: expected at least 6 fields", number + 1));
+            }
+
+            let value = |index: usize| {
+                fields[index]
+                    .parse::<f64>()
+                    .map_err(|err| format!("line {}: {}: {}", number + 1, fields[index], err))
+            };
+
+            let frame = value(0)? as u64;
+            if frame == 0 {
+                return Err(format!("line {}: MOT frames start at 1", number + 1));
+            }
+
+            truth
+                .frames
+                .entry(frame - 1)
+                .or_default()
+                .push(GroundTruthObject {
+                    id: value(1)? as u64,
+                    x: value(2)?.round() as i32,
+                    y: value(3)?.round() as i32,
+                    width: value(4)?.round() as i32,
+                    height: value(5)?.round() as i32,
+                });
+        }
+
+        if let (Some(&first), Some(&last)) =
+            (truth.frames.keys().next(), truth.frames.keys().last())
+        {
+            for frame in first..=last {
+                truth.frames.entry(frame).or_default();
+            }
+        }
+
+        Ok(truth)
+    }
+
+    /// 从 JSON 文件读过线事件, 内容是 `[{"frame": 120, "direction": "down"}, ...]`
+    pub fn load_events(&mut self, path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let text =
+            fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+
+        let events: Vec<GroundTruthEvent> =
+            serde_json::from_str(&text).map_err(|err| parse_error(path, err))?;
+        self.events.extend(events);
+
+        Ok(())
+    }
+
+    /// 最后一个需要处理的帧
+    pub fn last_frame(&self) -> Option<u64> {
+        let frame = self.frames.keys().last().copied();
+        let event = self.events.iter().map(|event| event.frame).max();
+
+        frame.max(event)
+    }
+
+    /// 标注的分方向数量
+    pub fn counts(&self) -> Counts {
+        let mut counts = Counts::default();
+        for event in &self.events {
+            counts.add(event.direction);
+        }
+
+        counts
+    }
+}
+
+/// 按交并比从大到小贪心匹配, 返回 (标注下标, 预测下标)
+fn match_boxes(truth: &[Rect], predicted: &[Rect], threshold: f64) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    for (i, a) in truth.iter().enumerate() {
+        for (j, b) in predicted.iter().enumerate() {
+            let overlap = iou(a, b);
+            if overlap >= threshold {
+                pairs.push((overlap, i, j));
+            }
+        }
+    }
+    pairs.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut used_truth = vec![false; truth.len()];
+    let mut used_predicted = vec![false; predicted.len()];
+    let mut matches = Vec::new();
+    for (_, i, j) in pairs {
+        if used_truth[i] || used_predicted[j] {
+            continue;
+        }
+        used_truth[i] = true;
+        used_predicted[j] = true;
+        matches.push((i, j));
+    }
+
+    matches
+}
+
+/// 匈牙利算法求最大权匹配, 返回最大的权重和
+fn max_assignment(weights: &[Vec<u64>]) -> u64 {
+    let rows = weights.len();
+    let cols = weights.first().map_or(0, Vec::len);
+    let n = rows.max(cols);
+    if n == 0 {
+        return 0;
+    }
+
+    // 补成方阵, 最大化转成最小化
+    let max = weights.iter().flatten().copied().max().unwrap_or(0) as i64;
+    let cost = |i: usize, j: usize| {
+        let weight = if i < rows && j < cols {
+            weights[i][j] as i64
+        } else {
+            0
+        };
+        max - weight
+    };
+
+    // 下标从1开始, 0 是哨兵
+    let mut u = vec![0i64; n + 1];
+    let mut v = vec![0i64; n + 1];
+    let mut matched = vec![0usize; n + 1];
+    let mut way = vec![0usize; n + 1];
+
+    for i in 1..=n {
+        matched[0] = i;
+        let mut j0 = 0;
+        let mut min = vec![i64::MAX; n + 1];
+        let mut used = vec![false; n + 1];
+
+        loop {
+            used[j0] = true;
+            let i0 = matched[j0];
+            let mut delta = i64::MAX;
+            let mut j1 = 0;
+
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+                let current = cost(i0 - 1, j - 1) - u[i0] - v[j];
+                if current < min[j] {
+                    min[j] = current;
+                    way[j] = j0;
+                }
+                if min[j] < delta {
+                    delta = min[j];
+                    j1 = j;
+                }
+            }
+
+            for j in 0..=n {
+                if used[j] {
+                    u[matched[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min[j] -= delta;
+                }
+            }
+
+            j0 = j1;
+            if matched[j0] == 0 {
+                break;
+            }
+        }
+
+        while j0 != 0 {
+            let j1 = way[j0];
+            matched[j0] = matched[j1];
+            j0 = j1;
+        }
+    }
+
+    (1..=n)
+        .filter(|&j| matched[j] - 1 < rows && j - 1 < cols)
+        .map(|j| weights[matched[j] - 1][j - 1])
+        .sum()
+}
+
+fn ratio(numerator: f64, denominator: f64) -> f64 {
+    if denominator > 0f64 {
+        numerator / denominator
+    } else {
+        0f64
+    }
+}
+
+/// 检测准确率, 每帧按交并比匹配
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct DetectionMetrics {
+    pub true_positives: u64,
+    pub false_positives: u64,
+    pub false_negatives: u64,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+}
+
+/// 跟踪准确率, MOTA 按 CLEAR MOT 计算, IDF1 按整条轨迹一对一匹配计算
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct TrackingMetrics {
+    /// 标注目标的总数(按帧累加)
+    pub objects: u64,
+    pub misses: u64,
+    pub false_positives: u64,
+    pub id_switches: u64,
+    pub mota: f64,
+    pub idtp: u64,
+    pub idfp: u64,
+    pub idfn: u64,
+    pub idf1: f64,
+}
+
+/// 计数误差
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct CountingMetrics {
+    pub truth: Counts,
+    pub predicted: Counts,
+    /// 两个方向的绝对误差之和
+    pub error: usize,
+    /// `error` 除以标注总数
+    pub relative_error: f64,
+}
+
+/// 一次评估的结果, 带上配置方便对比不同版本
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EvalReport {
+    pub label: String,
+    pub frames: u64,
+    pub iou_threshold: f64,
+    pub detection: DetectionMetrics,
+    pub tracking: TrackingMetrics,
+    pub counting: CountingMetrics,
+    pub config: PipelineConfig,
+}
+
+impl EvalReport {
+    pub fn write_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = io::BufWriter::new(fs::File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+
+    /// 往 CSV 里追加一行, 文件不存在时先写表头, 多次评估的结果放在一起对比
+    pub fn append_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let exists = path.exists();
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        if !exists {
+            writeln!(
+                file,
+                "label,frames,iou_threshold,precision,recall,f1,mota,idf1,id_switches,\
+                 truth_down,truth_up,predicted_down,predicted_up,count_error,relative_count_error"
+            )?;
+        }
+
+        let counting = &self.counting;
+        writeln!(
+            file,
+            "{},{},{},{:.4},{:.4},{:.4},{:.4},{:.4},{},{},{},{},{},{},{:.4}",
+            crate::export::csv_field(&self.label),
+            self.frames,
+            self.iou_threshold,
+            self.detection.precision,
+            self.detection.recall,
+            self.detection.f1,
+            self.tracking.mota,
+            self.tracking.idf1,
+            self.tracking.id_switches,
+            counting.truth.down,
+            counting.truth.up,
+            counting.predicted.down,
+            counting.predicted.up,
+            counting.error,
+            counting.relative_error
+        )
+    }
+}
+
+/// 逐帧累计评估指标
+///
+/// 每处理一帧调用 [`Evaluator::update`], 最后用 [`Evaluator::finish`] 算出结果
+pub struct Evaluator {
+    truth: GroundTruth,
+    iou_threshold: f64,
+    frames: u64,
+    detection: DetectionMetrics,
+    tracking: TrackingMetrics,
+    /// 标注编号 -> 上一次匹配到的轨迹编号, 用来数编号切换
+    assigned: HashMap<u64, u64>,
+    /// (标注编号, 轨迹编号) -> 交并比够大的帧数
+    overlaps: HashMap<(u64, u64), u64>,
+    predicted_objects: u64,
+}
+
+impl Evaluator {
+    pub fn new(truth: GroundTruth, iou_threshold: f64) -> Self {
+        Self {
+            truth,
+            iou_threshold,
+            frames: 0,
+            detection: DetectionMetrics::default(),
+            tracking: TrackingMetrics::default(),
+            assigned: HashMap::new(),
+            overlaps: HashMap::new(),
+            predicted_objects: 0,
+        }
+    }
+
+    /// 没有标注的帧直接跳过
+    pub fn update(&mut self, frame: u64, result: &FrameResult) {
+        let Some(objects) = self.truth.frames.get(&frame) else {
+            return;
+        };
+        self.frames += 1;
+
+        let truth: Vec<Rect> = objects.iter().map(GroundTruthObject::rect).collect();
+
+        // 检测
+        let detections: Vec<Rect> = result.detections.iter().map(|d| d.rect).collect();
+        let matched = match_boxes(&truth, &detections, self.iou_threshold).len() as u64;
+        self.detection.true_positives += matched;
+        self.detection.false_positives += detections.len() as u64 - matched;
+        self.detection.false_negatives += truth.len() as u64 - matched;
+
+        // 跟踪: 上一帧的对应关系还成立就保留, 剩下的重新匹配
+        let tracks: Vec<Rect> = result.tracks.iter().map(|t| t.rect).collect();
+        let mut used_truth = vec![false; truth.len()];
+        let mut used_tracks = vec![false; tracks.len()];
+        let mut pairs = Vec::new();
+
+        for (i, object) in objects.iter().enumerate() {
+            let Some(&track_id) = self.assigned.get(&object.id) else {
+                continue;
+            };
+            let Some(j) = result.tracks.iter().position(|t| t.id == track_id) else {
+                continue;
+            };
+            if !used_tracks[j] && iou(&truth[i], &tracks[j]) >= self.iou_threshold {
+                used_truth[i] = true;
+                used_tracks[j] = true;
+                pairs.push((i, j));
+            }
+        }
+
+        let rest_truth: Vec<usize> = (0..truth.len()).filter(|&i| !used_truth[i]).collect();
+        let rest_tracks: Vec<usize> = (0..tracks.len()).filter(|&j| !used_tracks[j]).collect();
+        let matches = match_boxes(
+            &rest_truth.iter().map(|&i| truth[i]).collect::<Vec<_>>(),
+            &rest_tracks.iter().map(|&j| tracks[j]).collect::<Vec<_>>(),
+            self.iou_threshold,
+        );
+        for (i, j) in matches {
+            let (i, j) = (rest_truth[i], rest_tracks[j]);
+            let object_id = objects[i].id;
+            let track_id = result.tracks[j].id;
+            if self
+                .assigned
+                .insert(object_id, track_id)
+                .is_some_and(|previous| previous != track_id)
+            {
+                self.tracking.id_switches += 1;
+            }
+            pairs.push((i, j));
+        }
+
+        self.tracking.objects += truth.len() as u64;
+        self.tracking.misses += (truth.len() - pairs.len()) as u64;
+        self.tracking.false_positives += (tracks.len() - pairs.len()) as u64;
+
+        // IDF1 要整条轨迹一起匹配, 先记下每一帧所有交并比够大的组合
+        self.predicted_objects += tracks.len() as u64;
+        for (i, object) in objects.iter().enumerate() {
+            for (j, track) in result.tracks.iter().enumerate() {
+                if iou(&truth[i], &tracks[j]) >= self.iou_threshold {
+                    *self.overlaps.entry((object.id, track.id)).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    /// `label` 用来区分不同的配置版本, `counts` 是流水线最后的计数
+    pub fn finish(&self, label: &str, config: &PipelineConfig, counts: Counts) -> EvalReport {
+        let mut detection = self.detection;
+        let tp = detection.true_positives as f64;
+        detection.precision = ratio(tp, tp + detection.false_positives as f64);
+        detection.recall = ratio(tp, tp + detection.false_negatives as f64);
+        detection.f1 = ratio(
+            2f64 * detection.precision * detection.recall,
+            detection.precision + detection.recall,
+        );
+
+        let mut tracking = self.tracking;
+        if tracking.objects > 0 {
+            let errors = tracking.misses + tracking.false_positives + tracking.id_switches;
+            tracking.mota = 1f64 - errors as f64 / tracking.objects as f64;
+        }
+
+        let mut object_ids: Vec<u64> = self.overlaps.keys().map(|&(object, _)| object).collect();
+        let mut track_ids: Vec<u64> = self.overlaps.keys().map(|&(_, track)| track).collect();
+        object_ids.sort_unstable();
+        object_ids.dedup();
+        track_ids.sort_unstable();
+        track_ids.dedup();
+        let weights: Vec<Vec<u64>> = object_ids
+            .iter()
+            .map(|object| {
+                track_ids
+                    .iter()
+                    .map(|track| self.overlaps.get(&(*object, *track)).copied().unwrap_or(0))
+                    .collect()
+            })
+            .collect();
+
+        tracking.idtp = max_assignment(&weights);
+        tracking.idfp = self.predicted_objects - tracking.idtp;
+        tracking.idfn = tracking.objects - tracking.idtp;
+        tracking.idf1 = ratio(
+            2f64 * tracking.idtp as f64,
+            (2 * tracking.idtp + tracking.idfp + tracking.idfn) as f64,
+        );
+
+        let truth = self.truth.counts();
+        let error = truth.down.abs_diff(counts.down) + truth.up.abs_diff(counts.up);
+        let counting = CountingMetrics {
+            truth,
+            predicted: counts,
+            error,
+            relative_error: ratio(error as f64, truth.total() as f64),
+        };
+
+        EvalReport {
+            label: label.to_string(),
+            frames: self.frames,
+            iou_threshold: self.iou_threshold,
+            detection,
+            tracking,
+            counting,
+            config: config.clone(),
+        }
+    }
+}
+
+/// 用一份配置把输入从头跑到最后一个标注帧, 算出评估结果
+pub fn evaluate(
+    input: &str,
+    fps: Option<f64>,
+    config: &PipelineConfig,
+    truth: &GroundTruth,
+    iou_threshold: f64,
+    label: &str,
+) -> opencv::Result<EvalReport> {
+    let mut source = open_source(input, fps)?;
+    let mut counter = VehicleCounter::from_config(config)?;
+    counter.set_fps(source.fps());
+
+    let last_frame = truth.last_frame();
+    let mut evaluator = Evaluator::new(truth.clone(), iou_threshold);
+
+    // 标注之后的帧不用处理, 计数也只算到这里
+    while let Some(Frame { index, time, image }) = source.read()? {
+        let result = counter.process_frame_at(&image, time)?;
+        evaluator.update(index, &result);
+
+        if last_frame.is_some_and(|last| index >= last) {
+            break;
+        }
+    }
+
+    Ok(evaluator.finish(label, config, counter.counts()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classify::VehicleClass;
+    use crate::pipeline::Detection;
+    use crate::tracker::{Track, Tracker};
+    use opencv::core::Point;
+
+    fn object(id: u64, rect: Rect) -> GroundTruthObject {
+        GroundTruthObject {
+            id,
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            height: rect.height,
+        }
+    }
+
+    /// 编号和位置随意指定的轨迹
+    fn track(id: u64, rect: Rect) -> Track {
+        let mut tracker = Tracker::new(0.3, 2000f64, 0.4);
+        tracker.update(
+            &[Detection {
+                rect,
+                center: Point::new(rect.x + rect.width / 2, rect.y + rect.height / 2),
+                class: VehicleClass::Car,
+            }],
+            0f64,
+        );
+
+        let mut track = tracker.tracks()[0].clone();
+        track.id = id;
+        track
+    }
+
+    #[test]
+    fn assignment_2x2_beats_greedy() {
+        // 贪心先拿 10, 剩下只能配 0
+        assert_eq!(max_assignment(&[vec![10, 9], vec![9, 0]]), 18);
+    }
+
+    #[test]
+    fn assignment_3x3_beats_greedy() {
+        // 贪心是 10 + 0 + 1, 最优是 8 + 8 + 1
+        let weights = vec![vec![10, 8, 0], vec![8, 0, 0], vec![0, 0, 1]];
+        assert_eq!(max_assignment(&weights), 17);
+    }
+
+    #[test]
+    fn assignment_rectangular() {
+        let wide = vec![vec![5, 4, 0], vec![4, 0, 0]];
+        assert_eq!(max_assignment(&wide), 8);
+
+        let tall = vec![vec![5, 4], vec![4, 0], vec![0, 0]];
+        assert_eq!(max_assignment(&tall), 8);
+
+        assert_eq!(max_assignment(&[vec![3, 7, 2]]), 7);
+        assert_eq!(max_assignment(&[]), 0);
+    }
+
+    #[test]
+    fn id_switch_between_two_frames() {
+        let rect = Rect::new(100, 100, 50, 40);
+        let mut truth = GroundTruth::default();
+        truth.frames.insert(0, vec![object(1, rect)]);
+        truth.frames.insert(1, vec![object(1, rect)]);
+
+        // 同一个目标第二帧换了轨迹编号
+        let mut evaluator = Evaluator::new(truth, 0.5);
+        for (frame, track_id) in [(0, 1), (1, 2)] {
+            let result = FrameResult {
+                tracks: vec![track(track_id, rect)],
+                ..FrameResult::default()
+            };
+            evaluator.update(frame, &result);
+        }
+
+        let report = evaluator.finish("test", &PipelineConfig::default(), Counts::default());
+        let tracking = report.tracking;
+        assert_eq!(report.frames, 2);
+        assert_eq!(tracking.objects, 2);
+        assert_eq!(tracking.misses, 0);
+        assert_eq!(tracking.false_positives, 0);
+        assert_eq!(tracking.id_switches, 1);
+        assert!((tracking.mota - 0.5).abs() < 1e-9);
+
+        // 一对一匹配时标注1只能配一条轨迹, 另一帧算 IDFP 和 IDFN
+        assert_eq!(tracking.idtp, 1);
+        assert_eq!(tracking.idfp, 1);
+        assert_eq!(tracking.idfn, 1);
+        assert!((tracking.idf1 - 0.5).abs() < 1e-9);
+    }
+}
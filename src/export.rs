@@ -0,0 +1,457 @@
+//! 每帧的检测结果导出成 JSON Lines 或者 CSV, 给数据分析用
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use opencv::core::{Point, Rect};
+use serde::Serialize;
+
+use crate::classify::{ClassCounts, VehicleClass};
+use crate::counting::{CountEvent, Counts, Direction, LineCount};
+use crate::incident::{Incident, IncidentKind};
+use crate::pipeline::{Detection, FrameResult};
+use crate::speed::SpeedEvent;
+use crate::tracker::Track;
+
+/// 外接矩形, 像素坐标
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BoxRecord {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl From<Rect> for BoxRecord {
+    fn from(rect: Rect) -> Self {
+        Self {
+            x: rect.x,
+            y: rect.y,
+            width: rect.width,
+            height: rect.height,
+        }
+    }
+}
+
+/// 一个点, 像素坐标
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PointRecord {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl From<Point> for PointRecord {
+    fn from(point: Point) -> Self {
+        Self {
+            x: point.x,
+            y: point.y,
+        }
+    }
+}
+
+/// 一个检测框
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DetectionRecord {
+    #[serde(rename = "box")]
+    pub rect: BoxRecord,
+    pub center: PointRecord,
+    pub class: VehicleClass,
+}
+
+impl From<&Detection> for DetectionRecord {
+    fn from(detection: &Detection) -> Self {
+        Self {
+            rect: detection.rect.into(),
+            center: detection.center.into(),
+            class: detection.class,
+        }
+    }
+}
+
+/// 一条轨迹在这一帧的位置
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrackRecord {
+    pub id: u64,
+    #[serde(rename = "box")]
+    pub rect: BoxRecord,
+    pub center: PointRecord,
+    pub class: VehicleClass,
+    /// 公里每小时
+    pub speed: Option<f64>,
+    /// 所在车道的名字, 没有配置车道或者不在任何车道里是 `None`
+    pub lane: Option<String>,
+}
+
+impl TrackRecord {
+    /// `lanes` 是 [`FrameResult::lanes`], 顺序和配置里的车道一样
+    pub fn new(track: &Track, lanes: &[LineCount]) -> Self {
+        Self {
+            id: track.id,
+            rect: track.rect.into(),
+            center: track.center().into(),
+            class: track.class(),
+            speed: track.speed,
+            lane: track
+                .lane
+                .and_then(|lane| lanes.get(lane))
+                .map(|lane| lane.name.clone()),
+        }
+    }
+}
+
+/// 一次计数
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EventRecord {
+    pub track_id: u64,
+    pub zone: String,
+    pub direction: Option<Direction>,
+    pub class: VehicleClass,
+    /// 车辆截图的路径, 没有保存快照时是 `None`
+    pub snapshot: Option<String>,
+    /// 画上外框的整帧截图的路径
+    pub frame_snapshot: Option<String>,
+}
+
+impl From<&CountEvent> for EventRecord {
+    fn from(event: &CountEvent) -> Self {
+        Self {
+            track_id: event.track_id,
+            zone: event.zone.clone(),
+            direction: event.direction,
+            class: event.class,
+            snapshot: None,
+            frame_snapshot: None,
+        }
+    }
+}
+
+/// 一次超速
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpeedingRecord {
+    pub track_id: u64,
+    /// 公里每小时
+    pub speed: f64,
+    pub limit: f64,
+}
+
+impl From<&SpeedEvent> for SpeedingRecord {
+    fn from(event: &SpeedEvent) -> Self {
+        Self {
+            track_id: event.track_id,
+            speed: event.speed,
+            limit: event.limit,
+        }
+    }
+}
+
+/// 一次停车, 逆行或者排队, 事件日志里每行一个
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IncidentRecord {
+    /// 帧号, 从0开始
+    pub frame: u64,
+    /// 在视频里的时间, 毫秒
+    pub timestamp_ms: f64,
+    pub kind: IncidentKind,
+    pub track_ids: Vec<u64>,
+    pub location: Option<String>,
+    /// 停了多少秒
+    pub duration: Option<f64>,
+    pub direction: Option<Direction>,
+    /// 排队长度, 有测速标定时是米, 否则是像素
+    pub length: Option<f64>,
+    #[serde(rename = "box")]
+    pub rect: BoxRecord,
+    /// 快照文件路径, 没有保存快照时是 `None`
+    pub snapshot: Option<String>,
+}
+
+impl IncidentRecord {
+    pub fn new(frame: u64, incident: &Incident, snapshot: Option<String>) -> Self {
+        Self {
+            frame,
+            timestamp_ms: incident.time * 1000f64,
+            kind: incident.kind,
+            track_ids: incident.track_ids.clone(),
+            location: incident.location.clone(),
+            duration: incident.duration,
+            direction: incident.direction,
+            length: incident.length,
+            rect: incident.rect.into(),
+            snapshot,
+        }
+    }
+}
+
+/// 事件日志, 每个事件一行 JSON
+pub struct IncidentLog {
+    writer: BufWriter<File>,
+}
+
+impl IncidentLog {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+        })
+    }
+
+    pub fn write(&mut self, record: &IncidentRecord) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        writeln!(self.writer)
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// 一帧的全部结果, JSON Lines 里每行一个
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FrameRecord {
+    /// 帧号, 从0开始
+    pub frame: u64,
+    /// 在视频里的时间, 毫秒
+    pub timestamp_ms: f64,
+    pub detections: Vec<DetectionRecord>,
+    pub tracks: Vec<TrackRecord>,
+    pub events: Vec<EventRecord>,
+    pub speeding: Vec<SpeedingRecord>,
+    /// 到这一帧为止的累计数量
+    pub counts: Counts,
+    pub classes: ClassCounts,
+}
+
+impl FrameRecord {
+    pub fn new(frame: u64, timestamp_ms: f64, result: &FrameResult) -> Self {
+        Self {
+            frame,
+            timestamp_ms,
+            detections: result.detections.iter().map(Into::into).collect(),
+            tracks: result
+                .tracks
+                .iter()
+                .map(|track| TrackRecord::new(track, &result.lanes))
+                .collect(),
+            events: result.counted.iter().map(Into::into).collect(),
+            speeding: result.speeding.iter().map(Into::into).collect(),
+            counts: result.counts,
+            classes: result.classes,
+        }
+    }
+}
+
+/// 逐帧结果的去处
+pub trait RecordSink {
+    fn write(&mut self, record: &FrameRecord) -> io::Result<()>;
+
+    /// 全部写完之后调用, 把缓冲区刷到文件
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+/// 每帧一行 JSON
+pub struct JsonLinesSink<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonLinesSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> RecordSink for JsonLinesSink<W> {
+    fn write(&mut self, record: &FrameRecord) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        writeln!(self.writer)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// CSV 表头, 每个检测框 / 轨迹 / 计数各占一行, 用 `kind` 区分, 用不到的列留空;
+/// 计数的 `snapshot` 是车辆截图的路径, `frame_snapshot` 是整帧截图的路径, 轨迹的 `lane` 是所在车道
+const CSV_HEADER: &str =
+    "frame,timestamp_ms,kind,id,x,y,width,height,center_x,center_y,zone,direction,class,speed_kmh,snapshot,frame_snapshot,lane";
+
+/// 每个检测框, 轨迹, 计数事件各一行
+pub struct CsvSink<W: Write> {
+    writer: W,
+    header_written: bool,
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            header_written: false,
+        }
+    }
+}
+
+/// 名字里有逗号或者引号时加上引号
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl<W: Write> RecordSink for CsvSink<W> {
+    fn write(&mut self, record: &FrameRecord) -> io::Result<()> {
+        if !self.header_written {
+            writeln!(self.writer, "{}", CSV_HEADER)?;
+            self.header_written = true;
+        }
+
+        let prefix = format!("{},{:.3}", record.frame, record.timestamp_ms);
+
+        for detection in &record.detections {
+            let BoxRecord {
+                x,
+                y,
+                width,
+                height,
+            } = detection.rect;
+            writeln!(
+                self.writer,
+                "{},detection,,{},{},{},{},{},{},,,{},,,,",
+                prefix,
+                x,
+                y,
+                width,
+                height,
+                detection.center.x,
+                detection.center.y,
+                detection.class.as_str()
+            )?;
+        }
+
+        for track in &record.tracks {
+            let BoxRecord {
+                x,
+                y,
+                width,
+                height,
+            } = track.rect;
+            writeln!(
+                self.writer,
+                "{},track,{},{},{},{},{},{},{},,,{},{},,,{}",
+                prefix,
+                track.id,
+                x,
+                y,
+                width,
+                height,
+                track.center.x,
+                track.center.y,
+                track.class.as_str(),
+                track
+                    .speed
+                    .map(|speed| format!("{:.1}", speed))
+                    .unwrap_or_default(),
+                track.lane.as_deref().map(csv_field).unwrap_or_default()
+            )?;
+        }
+
+        for event in &record.events {
+            writeln!(
+                self.writer,
+                "{},event,{},,,,,,,{},{},{},,{},{},",
+                prefix,
+                event.track_id,
+                csv_field(&event.zone),
+                event.direction.map(|d| d.as_str()).unwrap_or_default(),
+                event.class.as_str(),
+                event.snapshot.as_deref().map(csv_field).unwrap_or_default(),
+                event
+                    .frame_snapshot
+                    .as_deref()
+                    .map(csv_field)
+                    .unwrap_or_default()
+            )?;
+        }
+
+        for event in &record.speeding {
+            writeln!(
+                self.writer,
+                "{},speeding,{},,,,,,,,,,{:.1},,,",
+                prefix, event.track_id, event.speed
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// 按扩展名选择格式: `.csv` 写 CSV, 其他都写 JSON Lines
+pub fn record_sink(path: impl AsRef<Path>) -> io::Result<Box<dyn RecordSink>> {
+    let path = path.as_ref();
+    let writer = BufWriter::new(File::create(path)?);
+
+    let sink: Box<dyn RecordSink> = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("csv") => Box::new(CsvSink::new(writer)),
+        _ => Box::new(JsonLinesSink::new(writer)),
+    };
+
+    Ok(sink)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_rows_match_header() {
+        let record = FrameRecord {
+            frame: 3,
+            timestamp_ms: 120f64,
+            detections: vec![DetectionRecord {
+                rect: Rect::new(1, 2, 3, 4).into(),
+                center: Point::new(2, 4).into(),
+                class: VehicleClass::Car,
+            }],
+            tracks: vec![TrackRecord {
+                id: 7,
+                rect: Rect::new(1, 2, 3, 4).into(),
+                center: Point::new(2, 4).into(),
+                class: VehicleClass::Car,
+                speed: Some(40f64),
+                lane: Some("left".to_string()),
+            }],
+            events: vec![EventRecord {
+                track_id: 7,
+                zone: "line".to_string(),
+                direction: Some(Direction::Down),
+                class: VehicleClass::Car,
+                snapshot: Some("shots/7_120.jpg".to_string()),
+                frame_snapshot: Some("shots/7_120_frame.jpg".to_string()),
+            }],
+            speeding: vec![SpeedingRecord {
+                track_id: 7,
+                speed: 72f64,
+                limit: 60f64,
+            }],
+            counts: Counts::default(),
+            classes: ClassCounts::default(),
+        };
+
+        let mut sink = CsvSink::new(Vec::new());
+        sink.write(&record).unwrap();
+        let text = String::from_utf8(sink.writer).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        let columns = CSV_HEADER.split(',').count();
+        assert_eq!(lines.len(), 5);
+        for line in &lines {
+            assert_eq!(line.split(',').count(), columns, "{}", line);
+        }
+        assert!(lines[2].ends_with(",40.0,,,left"));
+        assert!(lines[3].ends_with(",shots/7_120.jpg,shots/7_120_frame.jpg,"));
+    }
+}
@@ -0,0 +1,170 @@
+use opencv::core::Point;
+
+/// 三点叉积, 大于0说明 `p` 在有向线段 `a -> b` 的右侧(图像坐标系y轴朝下)
+pub fn orientation(a: Point, b: Point, p: Point) -> i64 {
+    let abx = (b.x - a.x) as i64;
+    let aby = (b.y - a.y) as i64;
+    let apx = (p.x - a.x) as i64;
+    let apy = (p.y - a.y) as i64;
+
+    abx * apy - aby * apx
+}
+
+/// 移动轨迹 `from -> to` 是否穿过线段 `a -> b`
+///
+/// 返回值是 `from` 所在一侧的叉积符号, 不相交返回 `None`;
+/// 起点正好压在线上不算, 终点压在线上算, 这样相邻两段移动不会重复计数
+pub fn segment_crossing(a: Point, b: Point, from: Point, to: Point) -> Option<i64> {
+    let o1 = orientation(a, b, from);
+    let o2 = orientation(a, b, to);
+
+    if o1 == 0 || o1.signum() == o2.signum() {
+        return None;
+    }
+
+    let o3 = orientation(from, to, a);
+    let o4 = orientation(from, to, b);
+
+    if o3.signum() * o4.signum() > 0 {
+        return None;
+    }
+
+    Some(o1.signum())
+}
+
+/// 射线法判断点是否在多边形内
+pub fn point_in_polygon(point: Point, polygon: &[Point]) -> bool {
+    if polygon.len() < 3 {
+        return false;
+    }
+
+    let (px, py) = (point.x as f64, point.y as f64);
+    let mut inside = false;
+    let mut j = polygon.len() - 1;
+
+    for i in 0..polygon.len() {
+        let (xi, yi) = (polygon[i].x as f64, polygon[i].y as f64);
+        let (xj, yj) = (polygon[j].x as f64, polygon[j].y as f64);
+
+        if (yi > py) != (yj > py) && px < (xj - xi) * (py - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+
+        j = i;
+    }
+
+    inside
+}
+
+/// 点 `p` 到线段 `a -> b` 的距离, 坐标可以是像素也可以是路面上的米
+pub fn segment_distance(p: [f64; 2], a: [f64; 2], b: [f64; 2]) -> f64 {
+    let (abx, aby) = (b[0] - a[0], b[1] - a[1]);
+    let (apx, apy) = (p[0] - a[0], p[1] - a[1]);
+    let length = abx * abx + aby * aby;
+
+    // 垂足落在线段外面时取最近的端点
+    let t = if length > 0f64 {
+        ((apx * abx + apy * aby) / length).clamp(0f64, 1f64)
+    } else {
+        0f64
+    };
+    let (dx, dy) = (apx - t * abx, apy - t * aby);
+
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// 是否有三个点在同一条直线上(重合的点也算), 这样的四个点求不出透视变换
+pub fn has_collinear_triple(points: &[[f64; 2]]) -> bool {
+    let n = points.len();
+    for i in 0..n {
+        for j in i + 1..n {
+            for k in j + 1..n {
+                let (a, b, c) = (points[i], points[j], points[k]);
+                let cross = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+                if cross.abs() < 1e-6 {
+                    return true;
+                }
+            }
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `from -> to` 和从 (0, 0) 往右画到 (100, 0) 的水平线, 图像坐标系里上方的 y 更小
+    fn crossing(from: (i32, i32), to: (i32, i32)) -> Option<i64> {
+        segment_crossing(
+            Point::new(0, 0),
+            Point::new(100, 0),
+            Point::new(from.0, from.1),
+            Point::new(to.0, to.1),
+        )
+    }
+
+    #[test]
+    fn crossing_reports_the_starting_side() {
+        // 从上往下, 起点在线的左侧
+        assert_eq!(crossing((50, -10), (50, 10)), Some(-1));
+        assert_eq!(crossing((50, 10), (50, -10)), Some(1));
+        // 斜着穿过也算
+        assert_eq!(crossing((20, -10), (80, 30)), Some(-1));
+    }
+
+    #[test]
+    fn no_crossing_on_the_same_side() {
+        assert_eq!(crossing((50, -30), (60, -10)), None);
+    }
+
+    #[test]
+    fn ending_on_the_line_counts_but_starting_on_it_does_not() {
+        assert_eq!(crossing((50, -10), (50, 0)), Some(-1));
+        // 停在线上
+        assert_eq!(crossing((50, 0), (50, 0)), None);
+        // 从线上离开
+        assert_eq!(crossing((50, 0), (50, 10)), None);
+    }
+
+    #[test]
+    fn crossing_through_an_endpoint_counts() {
+        assert_eq!(crossing((100, -10), (100, 10)), Some(-1));
+        assert_eq!(crossing((0, 10), (0, -10)), Some(1));
+    }
+
+    #[test]
+    fn missing_the_finite_line() {
+        // 和直线相交, 但是在线段外面
+        assert_eq!(crossing((150, -10), (150, 10)), None);
+        assert_eq!(crossing((-1, -10), (-1, 10)), None);
+    }
+
+    #[test]
+    fn points_in_polygon() {
+        let square = [
+            Point::new(0, 0),
+            Point::new(100, 0),
+            Point::new(100, 100),
+            Point::new(0, 100),
+        ];
+        assert!(point_in_polygon(Point::new(50, 50), &square));
+        assert!(!point_in_polygon(Point::new(150, 50), &square));
+        assert!(!point_in_polygon(Point::new(50, -1), &square));
+        assert!(!point_in_polygon(Point::new(50, 50), &square[..2]));
+    }
+
+    #[test]
+    fn distance_to_segment() {
+        assert_eq!(
+            segment_distance([50f64, 30f64], [0f64, 0f64], [100f64, 0f64]),
+            30f64
+        );
+        // 垂足在线段外面时取端点
+        assert_eq!(
+            segment_distance([130f64, 40f64], [0f64, 0f64], [100f64, 0f64]),
+            50f64
+        );
+    }
+}
@@ -0,0 +1,525 @@
+//! 规则事件: 停车, 逆行和停止线后面的排队长度
+use std::collections::{HashMap, HashSet};
+
+use opencv::core::{Point, Rect, StsError};
+use serde::Serialize;
+
+use crate::config::{to_points, PipelineConfig};
+use crate::counting::{CountEvent, Direction};
+use crate::geometry::{point_in_polygon, segment_distance};
+use crate::speed::GroundPlane;
+use crate::tracker::Track;
+
+/// 事件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IncidentKind {
+    /// 停着超过配置的秒数
+    Stopped,
+    /// 逆着检测线允许的方向过线
+    WrongWay,
+    /// 停止线后面有车排队
+    Queue,
+}
+
+impl IncidentKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IncidentKind::Stopped => "stopped",
+            IncidentKind::WrongWay => "wrong_way",
+            IncidentKind::Queue => "queue",
+        }
+    }
+}
+
+/// 一次事件
+#[derive(Debug, Clone, PartialEq)]
+pub struct Incident {
+    pub kind: IncidentKind,
+    /// 发生的时间, 秒
+    pub time: f64,
+    /// 相关的轨迹, 排队时是队伍里所有的车
+    pub track_ids: Vec<u64>,
+    /// 停车所在的区域, 逆行的检测线, 排队的停止线
+    pub location: Option<String>,
+    /// 停了多少秒
+    pub duration: Option<f64>,
+    /// 逆行的方向
+    pub direction: Option<Direction>,
+    /// 排队长度, 有测速标定时是米, 否则是像素
+    pub length: Option<f64>,
+    /// 相关车辆外接矩形的并集, 存快照用
+    pub rect: Rect,
+}
+
+/// 一条轨迹从什么时候开始停着
+struct Still {
+    /// 停下时的中心点
+    anchor: Point,
+    since: f64,
+    /// 这次停车已经报过了
+    reported: bool,
+}
+
+struct StoppedRule {
+    seconds: f64,
+    /// 只在这些区域里报, 空的就整个画面都报
+    zones: Vec<(String, Vec<Point>)>,
+}
+
+struct QueueRule {
+    line: String,
+    /// 停止线, 有标定时已经投影到路面上
+    stop_line: Vec<[f64; 2]>,
+    zone: Vec<Point>,
+    seconds: f64,
+    interval: f64,
+    /// 上次报排队的时间, 队伍散了就清掉
+    reported: Option<f64>,
+}
+
+/// 按轨迹判断停车, 逆行和排队, 每帧调用一次 [`IncidentDetector::update`]
+pub struct IncidentDetector {
+    still_distance: f64,
+    stopped: Option<StoppedRule>,
+    queue: Option<QueueRule>,
+    /// 检测线名字 -> 允许的方向
+    allowed: HashMap<String, Direction>,
+    /// 有标定时排队长度按米算
+    plane: Option<GroundPlane>,
+    still: HashMap<u64, Still>,
+    /// 最近一帧的排队长度
+    queue_length: Option<f64>,
+}
+
+fn zone_polygon(config: &PipelineConfig, name: &str) -> opencv::Result<Vec<Point>> {
+    config
+        .zones
+        .iter()
+        .find(|zone| zone.name == name)
+        .map(|zone| to_points(&zone.polygon))
+        .ok_or_else(|| opencv::Error::new(StsError, format!("unknown zone {:?}", name)))
+}
+
+/// 两个矩形的并集
+fn union(a: Rect, b: Rect) -> Rect {
+    let x = a.x.min(b.x);
+    let y = a.y.min(b.y);
+    let right = (a.x + a.width).max(b.x + b.width);
+    let bottom = (a.y + a.height).max(b.y + b.height);
+
+    Rect::new(x, y, right - x, bottom - y)
+}
+
+impl IncidentDetector {
+    /// 按配置创建, 配置需要先通过 [`PipelineConfig::validate`]
+    pub fn new(config: &PipelineConfig) -> opencv::Result<Self> {
+        let plane = config
+            .calibration
+            .as_ref()
+            .map(|calibration| GroundPlane::new(&calibration.image, &calibration.world))
+            .transpose()?;
+
+        let stopped = match &config.incidents.stopped {
+            Some(stopped) => Some(StoppedRule {
+                seconds: stopped.seconds,
+                zones: stopped
+                    .zones
+                    .iter()
+                    .map(|name| Ok((name.clone(), zone_polygon(config, name)?)))
+                    .collect::<opencv::Result<_>>()?,
+            }),
+            None => None,
+        };
+
+        let queue = match &config.incidents.queue {
+            Some(queue) => {
+                let line = config
+                    .lines
+                    .iter()
+                    .find(|line| line.name == queue.line)
+                    .ok_or_else(|| {
+                        opencv::Error::new(StsError, format!("unknown line {:?}", queue.line))
+                    })?;
+                let stop_line = to_points(&line.points)
+                    .into_iter()
+                    .map(|point| Self::locate(plane.as_ref(), point))
+                    .collect::<opencv::Result<_>>()?;
+
+                Some(QueueRule {
+                    line: queue.line.clone(),
+                    stop_line,
+                    zone: zone_polygon(config, &queue.zone)?,
+                    seconds: queue.seconds,
+                    interval: queue.interval,
+                    reported: None,
+                })
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            still_distance: config.incidents.still_distance,
+            stopped,
+            queue,
+            allowed: config
+                .lines
+                .iter()
+                .filter_map(|line| Some((line.name.clone(), line.allowed?)))
+                .collect(),
+            plane,
+            still: HashMap::new(),
+            queue_length: None,
+        })
+    }
+
+    /// 像素坐标, 有标定时换成路面坐标
+    fn locate(plane: Option<&GroundPlane>, point: Point) -> opencv::Result<[f64; 2]> {
+        match plane {
+            Some(plane) => {
+                let point = plane.project(point)?;
+                Ok([point.x as f64, point.y as f64])
+            }
+            None => Ok([point.x as f64, point.y as f64]),
+        }
+    }
+
+    /// 排队长度的单位
+    pub fn length_unit(&self) -> &'static str {
+        if self.plane.is_some() {
+            "m"
+        } else {
+            "px"
+        }
+    }
+
+    /// 最近一帧的排队长度, 没有配置排队时是 `None`
+    pub fn queue_length(&self) -> Option<f64> {
+        self.queue_length
+    }
+
+    /// `tracks` 是这一帧匹配到的轨迹, `counted` 是这一帧的计数, `time` 是这一帧的时间(秒)
+    pub fn update(
+        &mut self,
+        tracks: &[Track],
+        counted: &[CountEvent],
+        time: f64,
+    ) -> opencv::Result<Vec<Incident>> {
+        let mut incidents = Vec::new();
+
+        // 逆行: 过线方向和线上允许的方向相反
+        for event in counted {
+            let (Some(direction), Some(allowed)) = (event.direction, self.allowed.get(&event.zone))
+            else {
+                continue;
+            };
+            if direction == *allowed {
+                continue;
+            }
+            let Some(track) = tracks.iter().find(|track| track.id == event.track_id) else {
+                continue;
+            };
+
+            incidents.push(Incident {
+                kind: IncidentKind::WrongWay,
+                time,
+                track_ids: vec![track.id],
+                location: Some(event.zone.clone()),
+                duration: None,
+                direction: Some(direction),
+                length: None,
+                rect: track.rect,
+            });
+        }
+
+        // 中心点离开停下时的位置太远就重新计时
+        for track in tracks {
+            let center = track.center();
+            let still = self.still.entry(track.id).or_insert(Still {
+                anchor: center,
+                since: time,
+                reported: false,
+            });
+            let dx = (center.x - still.anchor.x) as f64;
+            let dy = (center.y - still.anchor.y) as f64;
+            if (dx * dx + dy * dy).sqrt() > self.still_distance {
+                *still = Still {
+                    anchor: center,
+                    since: time,
+                    reported: false,
+                };
+            }
+        }
+
+        if let Some(rule) = &self.stopped {
+            for track in tracks {
+                let still = self.still.get_mut(&track.id).unwrap();
+                let duration = time - still.since;
+                if still.reported || duration < rule.seconds {
+                    continue;
+                }
+
+                let center = track.center();
+                let zone = rule
+                    .zones
+                    .iter()
+                    .find(|(_, polygon)| point_in_polygon(center, polygon))
+                    .map(|(name, _)| name.clone());
+                if zone.is_none() && !rule.zones.is_empty() {
+                    continue;
+                }
+
+                still.reported = true;
+                incidents.push(Incident {
+                    kind: IncidentKind::Stopped,
+                    time,
+                    track_ids: vec![track.id],
+                    location: zone,
+                    duration: Some(duration),
+                    direction: None,
+                    length: None,
+                    rect: track.rect,
+                });
+            }
+        }
+
+        if let Some(rule) = &mut self.queue {
+            // 排队区域里停着的车, 队伍长度是离停止线最远的那个车角到停止线的距离
+            let mut length = 0f64;
+            let mut queued: Vec<&Track> = Vec::new();
+            for track in tracks {
+                let still = &self.still[&track.id];
+                if time - still.since < rule.seconds
+                    || !point_in_polygon(track.center(), &rule.zone)
+                {
+                    continue;
+                }
+
+                let Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                } = track.rect;
+                for corner in [
+                    Point::new(x, y),
+                    Point::new(x + width, y),
+                    Point::new(x, y + height),
+                    Point::new(x + width, y + height),
+                ] {
+                    let corner = Self::locate(self.plane.as_ref(), corner)?;
+                    let distance = rule
+                        .stop_line
+                        .windows(2)
+                        .map(|segment| segment_distance(corner, segment[0], segment[1]))
+                        .fold(f64::INFINITY, f64::min);
+                    length = length.max(distance);
+                }
+                queued.push(track);
+            }
+            self.queue_length = Some(length);
+
+            if queued.is_empty() {
+                rule.reported = None;
+            } else if rule
+                .reported
+                .is_none_or(|reported| time - reported >= rule.interval)
+            {
+                rule.reported = Some(time);
+                incidents.push(Incident {
+                    kind: IncidentKind::Queue,
+                    time,
+                    track_ids: queued.iter().map(|track| track.id).collect(),
+                    location: Some(rule.line.clone()),
+                    duration: None,
+                    direction: None,
+                    length: Some(length),
+                    rect: queued.iter().map(|track| track.rect).reduce(union).unwrap(),
+                });
+            }
+        }
+
+        Ok(incidents)
+    }
+
+    /// 丢掉已经消失的轨迹
+    pub fn retain(&mut self, alive: &HashSet<u64>) {
+        self.still.retain(|id, _| alive.contains(id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classify::VehicleClass;
+    use crate::config::{IncidentConfig, LineConfig, QueueConfig, StoppedConfig, ZoneConfig};
+    use crate::pipeline::{center, Detection};
+    use crate::tracker::Tracker;
+
+    /// 编号是 `id`, 外框是 `rect` 的轨迹
+    fn track(id: u64, rect: Rect) -> Track {
+        let mut tracker = Tracker::default();
+        tracker.update(
+            &[Detection {
+                rect,
+                center: center(&rect),
+                class: VehicleClass::Car,
+            }],
+            0f64,
+        );
+
+        let mut track = tracker.tracks()[0].clone();
+        track.id = id;
+        track
+    }
+
+    /// 停止线是 y = 550 的水平线, 线上方 400..550 是排队区域
+    fn config(incidents: IncidentConfig) -> PipelineConfig {
+        PipelineConfig {
+            lines: vec![LineConfig {
+                name: "line".to_string(),
+                points: vec![[10, 550], [1200, 550]],
+                allowed: Some(Direction::Down),
+            }],
+            zones: vec![ZoneConfig {
+                name: "stop".to_string(),
+                polygon: vec![[0, 400], [1300, 400], [1300, 550], [0, 550]],
+            }],
+            incidents,
+            ..PipelineConfig::default()
+        }
+    }
+
+    fn kinds(incidents: &[Incident]) -> Vec<IncidentKind> {
+        incidents.iter().map(|incident| incident.kind).collect()
+    }
+
+    #[test]
+    fn wrong_way_against_the_allowed_direction() {
+        let mut detector = IncidentDetector::new(&config(IncidentConfig::default())).unwrap();
+        let tracks = [track(1, Rect::new(100, 530, 40, 40))];
+        let event = |direction| CountEvent {
+            track_id: 1,
+            zone: "line".to_string(),
+            direction: Some(direction),
+            class: VehicleClass::Car,
+        };
+
+        let incidents = detector
+            .update(&tracks, &[event(Direction::Down)], 0f64)
+            .unwrap();
+        assert!(incidents.is_empty());
+
+        let incidents = detector
+            .update(&tracks, &[event(Direction::Up)], 0.04)
+            .unwrap();
+        assert_eq!(kinds(&incidents), [IncidentKind::WrongWay]);
+        assert_eq!(incidents[0].track_ids, [1]);
+        assert_eq!(incidents[0].direction, Some(Direction::Up));
+        assert_eq!(incidents[0].location.as_deref(), Some("line"));
+    }
+
+    #[test]
+    fn stopped_once_after_the_configured_seconds() {
+        let mut detector = IncidentDetector::new(&config(IncidentConfig {
+            stopped: Some(StoppedConfig {
+                seconds: 10f64,
+                zones: Vec::new(),
+            }),
+            ..IncidentConfig::default()
+        }))
+        .unwrap();
+
+        // 抖动不超过 still_distance 还算停着
+        let still = [track(1, Rect::new(100, 100, 40, 40))];
+        let jitter = [track(1, Rect::new(105, 103, 40, 40))];
+        assert!(detector.update(&still, &[], 0f64).unwrap().is_empty());
+        assert!(detector.update(&jitter, &[], 9.9).unwrap().is_empty());
+
+        let incidents = detector.update(&still, &[], 10f64).unwrap();
+        assert_eq!(kinds(&incidents), [IncidentKind::Stopped]);
+        assert_eq!(incidents[0].duration, Some(10f64));
+        assert_eq!(incidents[0].location, None);
+
+        // 同一次停车只报一次
+        assert!(detector.update(&still, &[], 20f64).unwrap().is_empty());
+
+        // 开走之后重新计时
+        let moved = [track(1, Rect::new(300, 100, 40, 40))];
+        assert!(detector.update(&moved, &[], 21f64).unwrap().is_empty());
+        assert!(detector.update(&moved, &[], 30f64).unwrap().is_empty());
+        assert_eq!(
+            kinds(&detector.update(&moved, &[], 31f64).unwrap()),
+            [IncidentKind::Stopped]
+        );
+    }
+
+    #[test]
+    fn stopped_only_inside_the_configured_zones() {
+        let mut detector = IncidentDetector::new(&config(IncidentConfig {
+            stopped: Some(StoppedConfig {
+                seconds: 1f64,
+                zones: vec!["stop".to_string()],
+            }),
+            ..IncidentConfig::default()
+        }))
+        .unwrap();
+
+        let tracks = [
+            track(1, Rect::new(100, 100, 40, 40)),
+            track(2, Rect::new(100, 450, 40, 40)),
+        ];
+        detector.update(&tracks, &[], 0f64).unwrap();
+        let incidents = detector.update(&tracks, &[], 1f64).unwrap();
+
+        assert_eq!(kinds(&incidents), [IncidentKind::Stopped]);
+        assert_eq!(incidents[0].track_ids, [2]);
+        assert_eq!(incidents[0].location.as_deref(), Some("stop"));
+    }
+
+    #[test]
+    fn queue_length_behind_the_stop_line() {
+        let mut detector = IncidentDetector::new(&config(IncidentConfig {
+            queue: Some(QueueConfig {
+                line: "line".to_string(),
+                zone: "stop".to_string(),
+                seconds: 2f64,
+                interval: 10f64,
+            }),
+            ..IncidentConfig::default()
+        }))
+        .unwrap();
+
+        // 两辆车停在停止线后面, 远的那辆车顶离线 100 像素
+        let tracks = [
+            track(1, Rect::new(100, 500, 40, 40)),
+            track(2, Rect::new(100, 450, 40, 40)),
+        ];
+        assert!(detector.update(&tracks, &[], 0f64).unwrap().is_empty());
+        assert_eq!(detector.queue_length(), Some(0f64));
+
+        let incidents = detector.update(&tracks, &[], 2f64).unwrap();
+        assert_eq!(kinds(&incidents), [IncidentKind::Queue]);
+        assert_eq!(incidents[0].track_ids, [1, 2]);
+        assert_eq!(incidents[0].length, Some(100f64));
+        assert_eq!(incidents[0].rect, Rect::new(100, 450, 40, 90));
+        assert_eq!(detector.length_unit(), "px");
+
+        // 每隔 interval 秒才再报一次
+        assert!(detector.update(&tracks, &[], 11f64).unwrap().is_empty());
+        assert_eq!(
+            kinds(&detector.update(&tracks, &[], 12f64).unwrap()),
+            [IncidentKind::Queue]
+        );
+
+        // 队伍散了之后再排起来马上就报
+        assert!(detector.update(&[], &[], 13f64).unwrap().is_empty());
+        assert_eq!(detector.queue_length(), Some(0f64));
+        detector.retain(&HashSet::new());
+        detector.update(&tracks, &[], 14f64).unwrap();
+        assert_eq!(
+            kinds(&detector.update(&tracks, &[], 16f64).unwrap()),
+            [IncidentKind::Queue]
+        );
+    }
+}
@@ -0,0 +1,34 @@
+//! 车辆目标检测库
+//!
+//! `src/bin/project_one.rs` 里的整条检测链路, 抽出来给其他服务直接复用
+pub mod background;
+pub mod blob;
+pub mod classify;
+pub mod cli;
+pub mod config;
+pub mod counting;
+pub mod detector;
+pub mod dnn;
+pub mod eval;
+pub mod export;
+pub mod geometry;
+pub mod incident;
+pub mod output;
+pub mod parallel;
+pub mod pipeline;
+pub mod roi;
+pub mod shadow;
+pub mod snapshot;
+pub mod source;
+pub mod speed;
+pub mod stats;
+pub mod summary;
+pub mod tracker;
+pub mod tune;
+
+pub use classify::{ClassCounts, VehicleClass};
+pub use config::{ConfigError, PipelineConfig};
+pub use counting::{CountEvent, CountLine, CountZone, Counts, Direction};
+pub use export::FrameRecord;
+pub use pipeline::{Detection, FrameResult, VehicleCounter};
+pub use tracker::{Track, Tracker};
@@ -1,11 +1,17 @@
-use opencv::core::{bitwise_not, no_array, Rect, Scalar, Vec3b, CV_8UC1, CV_8UC3, CV_8UC4};
-
-use opencv::highgui::{imshow, wait_key};
+use clap::Parser;
+use cv_demo::cli::OutputArgs;
+use opencv::core::{bitwise_not, no_array, Rect, Scalar, CV_8UC1};
 use opencv::prelude::*;
 
+#[derive(Parser)]
+#[command(about = "在黑色图片中间画一个白色方块再取反")]
+struct Cli {
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
 fn main() -> opencv::Result<()> {
-    // let mat = Mat::new_rows_cols_with_default(200, 200, CV_8UC1, 0.into())?;
-    // Mat::roi(&mat, Rect::new(50, 50, 100, 100))?.set_scalar(Scalar::from(255))?;
+    let cli = Cli::parse();
 
     let mat = Mat::new_rows_cols_with_default(200, 200, CV_8UC1, 0.into())?;
 
@@ -13,11 +19,9 @@ fn main() -> opencv::Result<()> {
 
     let mut ddd = Mat::default();
 
-    bitwise_not(&mat, &mut ddd, &no_array()).unwrap();
-
-    imshow("adasd", &ddd).unwrap();
+    bitwise_not(&mat, &mut ddd, &no_array())?;
 
-    wait_key(10000).unwrap();
+    cli.output.show("adasd", &ddd)?;
 
     Ok(())
 }
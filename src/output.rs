@@ -0,0 +1,232 @@
+//! 结果输出: 窗口显示, 或者不依赖图形界面直接写图片 / 视频文件
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use opencv::core::{Size, StsError};
+use opencv::highgui::{destroy_all_windows, imshow, wait_key};
+use opencv::imgcodecs::imwrite_def;
+use opencv::prelude::*;
+use opencv::videoio::VideoWriter;
+
+/// 按扩展名识别成视频的输出路径, 也用来在目录里找视频
+pub const VIDEO_EXTENSIONS: [&str; 4] = ["mp4", "avi", "mkv", "mov"];
+
+/// 按扩展名识别成单张图片的输出路径, 也用来识别图片序列输入
+pub const IMAGE_EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "bmp", "tiff"];
+
+/// 一帧画面的去处
+pub trait FrameSink {
+    /// 输出一帧, 返回 `false` 表示用户要求停止
+    fn write(&mut self, frame: &Mat) -> opencv::Result<bool>;
+
+    /// 全部输出完之后调用, 用于关闭文件或者窗口
+    fn finish(&mut self) -> opencv::Result<()> {
+        Ok(())
+    }
+}
+
+/// 保存图片, 写失败时返回错误而不是 `false`
+pub fn save_image(path: &str, mat: &Mat) -> opencv::Result<()> {
+    if !imwrite_def(path, mat)? {
+        return Err(opencv::Error::new(
+            StsError,
+            format!("cannot write image to {}", path),
+        ));
+    }
+
+    Ok(())
+}
+
+/// 用 `imshow` 显示, 需要图形界面
+pub struct WindowSink {
+    name: String,
+    /// 两帧之间间隔多少毫秒, 处理得慢就相应少等一会儿
+    delay: i32,
+    /// 上一帧显示完的时间
+    last: Option<Instant>,
+}
+
+impl WindowSink {
+    pub fn new(name: impl Into<String>, delay: i32) -> Self {
+        Self {
+            name: name.into(),
+            delay,
+            last: None,
+        }
+    }
+}
+
+impl FrameSink for WindowSink {
+    fn write(&mut self, frame: &Mat) -> opencv::Result<bool> {
+        imshow(&self.name, frame)?;
+
+        let wait = match self.last {
+            Some(last) => (self.delay - last.elapsed().as_millis() as i32).max(1),
+            None => self.delay.max(1),
+        };
+
+        // 按任意键停止
+        let key = wait_key(wait)?;
+        self.last = Some(Instant::now());
+        Ok(!(key > 0 && key != 255))
+    }
+
+    fn finish(&mut self) -> opencv::Result<()> {
+        destroy_all_windows()
+    }
+}
+
+/// 每次都覆盖写同一张图片, 适合只有一个结果的演示
+pub struct ImageSink {
+    path: String,
+}
+
+impl ImageSink {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl FrameSink for ImageSink {
+    fn write(&mut self, frame: &Mat) -> opencv::Result<bool> {
+        save_image(&self.path, frame)?;
+        Ok(true)
+    }
+}
+
+/// 每帧写一张图片到目录里, 文件名是 `frame_000000.png`
+pub struct ImageSequenceSink {
+    dir: PathBuf,
+    index: usize,
+}
+
+impl ImageSequenceSink {
+    pub fn new(dir: impl Into<PathBuf>) -> opencv::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).map_err(|err| {
+            opencv::Error::new(
+                StsError,
+                format!("cannot create directory {}: {}", dir.display(), err),
+            )
+        })?;
+
+        Ok(Self { dir, index: 0 })
+    }
+}
+
+impl FrameSink for ImageSequenceSink {
+    fn write(&mut self, frame: &Mat) -> opencv::Result<bool> {
+        let path = self.dir.join(format!("frame_{:06}.png", self.index));
+        save_image(&path.to_string_lossy(), frame)?;
+        self.index += 1;
+        Ok(true)
+    }
+}
+
+/// 用 `VideoWriter` 写视频文件, 第一帧到来时才知道画面大小, 所以延迟打开
+pub struct VideoSink {
+    path: String,
+    fps: f64,
+    writer: Option<VideoWriter>,
+}
+
+impl VideoSink {
+    pub fn new(path: impl Into<String>, fps: f64) -> Self {
+        Self {
+            path: path.into(),
+            fps,
+            writer: None,
+        }
+    }
+
+    fn open(&self, size: Size) -> opencv::Result<VideoWriter> {
+        let fourcc = if self.path.ends_with(".avi") {
+            VideoWriter::fourcc('M', 'J', 'P', 'G')?
+        } else {
+            VideoWriter::fourcc('m', 'p', '4', 'v')?
+        };
+
+        let writer = VideoWriter::new(&self.path, fourcc, self.fps, size, true)?;
+        if !writer.is_opened()? {
+            return Err(opencv::Error::new(
+                StsError,
+                format!("cannot open video writer for {}", self.path),
+            ));
+        }
+
+        Ok(writer)
+    }
+}
+
+impl FrameSink for VideoSink {
+    fn write(&mut self, frame: &Mat) -> opencv::Result<bool> {
+        if self.writer.is_none() {
+            self.writer = Some(self.open(frame.size()?)?);
+        }
+
+        if let Some(writer) = &mut self.writer {
+            writer.write(frame)?;
+        }
+
+        Ok(true)
+    }
+
+    fn finish(&mut self) -> opencv::Result<()> {
+        if let Some(writer) = &mut self.writer {
+            writer.release()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 同时输出到多个地方, 任意一个要求停止就停止
+#[derive(Default)]
+pub struct Sinks(Vec<Box<dyn FrameSink>>);
+
+impl Sinks {
+    pub fn push(&mut self, sink: impl FrameSink + 'static) {
+        self.push_boxed(Box::new(sink));
+    }
+
+    /// 加入已经装箱的输出, 比如 [`file_sink`] 的返回值
+    pub fn push_boxed(&mut self, sink: Box<dyn FrameSink>) {
+        self.0.push(sink);
+    }
+}
+
+impl FrameSink for Sinks {
+    fn write(&mut self, frame: &Mat) -> opencv::Result<bool> {
+        let mut keep_going = true;
+        for sink in &mut self.0 {
+            keep_going &= sink.write(frame)?;
+        }
+
+        Ok(keep_going)
+    }
+
+    fn finish(&mut self) -> opencv::Result<()> {
+        for sink in &mut self.0 {
+            sink.finish()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 按输出路径的扩展名选择: 视频文件, 单张图片, 没有扩展名就当作图片序列目录
+pub fn file_sink(path: &str, fps: f64) -> opencv::Result<Box<dyn FrameSink>> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase());
+
+    let sink: Box<dyn FrameSink> = match extension.as_deref() {
+        Some(ext) if VIDEO_EXTENSIONS.contains(&ext) => Box::new(VideoSink::new(path, fps)),
+        Some(ext) if IMAGE_EXTENSIONS.contains(&ext) => Box::new(ImageSink::new(path)),
+        _ => Box::new(ImageSequenceSink::new(path)?),
+    };
+
+    Ok(sink)
+}
@@ -0,0 +1,83 @@
+//! 多线程处理一组任务, `batch` 和 `tune` 共用
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// 用几个线程: 不传就等于 CPU 核数, 至少1个, 不超过任务数
+pub fn worker_count(jobs: Option<usize>, tasks: usize) -> usize {
+    jobs.unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .clamp(1, tasks.max(1))
+}
+
+/// 用 `jobs` 个线程对每个 `items` 调用 `work`, 结果按 `items` 原来的顺序返回
+///
+/// 每个线程从队列里取下一个任务, 处理慢的任务不会拖住别的线程;
+/// 每完成一个任务调用一次 `progress(已完成的数量, 任务, 结果)`
+pub fn map<T, R, F, P>(items: &[T], jobs: Option<usize>, work: F, progress: P) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+    P: Fn(usize, &T, &R) + Sync,
+{
+    let jobs = worker_count(jobs, items.len());
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    let results = Mutex::new((0..items.len()).map(|_| None).collect::<Vec<Option<R>>>());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+
+                let result = work(item);
+                let finished = done.fetch_add(1, Ordering::SeqCst) + 1;
+                progress(finished, item, &result);
+
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn worker_count_is_clamped() {
+        assert_eq!(worker_count(Some(8), 3), 3);
+        assert_eq!(worker_count(Some(0), 3), 1);
+        assert_eq!(worker_count(Some(2), 0), 1);
+        assert!(worker_count(None, 100) >= 1);
+    }
+
+    #[test]
+    fn map_keeps_order_and_reports_progress() {
+        let items: Vec<u64> = (0..50).collect();
+        let reported = Mutex::new(Vec::new());
+
+        let results = map(
+            &items,
+            Some(4),
+            |item| item * item,
+            |finished, _, _| reported.lock().unwrap().push(finished),
+        );
+
+        assert_eq!(results, items.iter().map(|i| i * i).collect::<Vec<_>>());
+        let mut reported = reported.into_inner().unwrap();
+        reported.sort_unstable();
+        assert_eq!(reported, (1..=50).collect::<Vec<_>>());
+        assert!(map(&[] as &[u64], None, |i| *i, |_, _, _| {}).is_empty());
+    }
+}
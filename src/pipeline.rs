@@ -0,0 +1,478 @@
+use opencv::core::{Point, Rect, Scalar, StsError};
+use opencv::imgproc::{
+    circle_def, polylines_def, put_text_def, rectangle_def, FONT_HERSHEY_SIMPLEX,
+};
+use opencv::prelude::*;
+use opencv::types::VectorOfPoint;
+
+use std::collections::HashSet;
+
+use crate::blob::Rejection;
+use crate::classify::{ClassCounts, VehicleClass};
+use crate::config::{to_points, PipelineConfig};
+use crate::counting::{
+    assign_lane, CountEvent, CountLine, CountZone, Counts, Lane, LineCount, ZoneCount,
+};
+use crate::detector::{create_detector, Detector, DetectorOutput};
+use crate::incident::{Incident, IncidentDetector};
+use crate::speed::{SpeedEstimator, SpeedEvent};
+use crate::tracker::{Track, Tracker};
+
+/// 获取矩形的中心点
+pub fn center(point: &Rect) -> Point {
+    let x = point.width / 2;
+    let y = point.height / 2;
+
+    let center_x = point.x + x;
+    let center_y = point.y + y;
+
+    Point {
+        x: center_x,
+        y: center_y,
+    }
+}
+
+/// 单个被检测到的车辆
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    /// 外接矩形
+    pub rect: Rect,
+    /// 中心点
+    pub center: Point,
+    /// 按这一帧的轮廓估计的车型
+    pub class: VehicleClass,
+}
+
+/// 处理一帧之后的结果
+#[derive(Debug, Clone, Default)]
+pub struct FrameResult {
+    /// 这一帧里通过过滤的车辆
+    pub detections: Vec<Detection>,
+    /// 这一帧里被过滤掉的轮廓和原因
+    pub rejected: Vec<Rejection>,
+    /// 这一帧匹配到的轨迹
+    pub tracks: Vec<Track>,
+    /// 这一帧刚刚过线被计数的轨迹
+    pub counted: Vec<CountEvent>,
+    /// 这一帧刚刚超速的轨迹
+    pub speeding: Vec<SpeedEvent>,
+    /// 这一帧发生的停车, 逆行和排队事件
+    pub incidents: Vec<Incident>,
+    /// 停止线后面的排队长度, 没有配置排队时是 `None`
+    pub queue: Option<f64>,
+    /// 到目前为止分方向的车辆总数, 同一辆车过多条线只算一次
+    pub counts: Counts,
+    /// 同上, 再按车型分开
+    pub classes: ClassCounts,
+    /// 每条检测线的累计数量
+    pub lines: Vec<LineCount>,
+    /// 每个区域的累计数量
+    pub zones: Vec<ZoneCount>,
+    /// 每条车道的累计数量, 和总数一样每辆车只算一次
+    pub lanes: Vec<LineCount>,
+}
+
+/// 车辆计数器
+///
+/// 检测 -> 跟踪 -> 过线计数 -> 测速 -> 规则事件, 检测器见 [`crate::detector`]
+pub struct VehicleCounter {
+    config: PipelineConfig,
+    detector: Box<dyn Detector>,
+    tracker: Tracker,
+    counts: Counts,
+    classes: ClassCounts,
+    lines: Vec<CountLine>,
+    zones: Vec<CountZone>,
+    lanes: Vec<Lane>,
+    speed: Option<SpeedEstimator>,
+    incidents: IncidentDetector,
+    /// 视频帧率, 没有时间戳时用来换算时间
+    fps: f64,
+    /// 已经处理了多少帧
+    frame_index: u64,
+}
+
+impl VehicleCounter {
+    /// 使用默认配置, 只有一条水平检测线, 从 (10, 550) 到 (1200, 550)
+    pub fn new() -> opencv::Result<Self> {
+        Self::from_config(&PipelineConfig::default())
+    }
+
+    /// 按配置创建, 配置不合法时返回 [`PipelineConfig::validate`] 的错误
+    pub fn from_config(config: &PipelineConfig) -> opencv::Result<Self> {
+        config
+            .validate()
+            .map_err(|err| opencv::Error::new(StsError, err.to_string()))?;
+
+        let tracker = Tracker::new(
+            config.tracker.iou_threshold,
+            config.tracker.max_speed,
+            config.tracker.max_age,
+        );
+
+        Ok(Self {
+            config: config.clone(),
+            detector: create_detector(config)?,
+            tracker,
+            counts: Counts::default(),
+            classes: ClassCounts::default(),
+            lines: config.lines.iter().map(|line| line.to_line()).collect(),
+            zones: config.zones.iter().map(|zone| zone.to_zone()).collect(),
+            lanes: config.lanes.iter().map(|lane| lane.to_lane()).collect(),
+            speed: config
+                .calibration
+                .as_ref()
+                .map(SpeedEstimator::new)
+                .transpose()?,
+            incidents: IncidentDetector::new(config)?,
+            fps: 25f64,
+            frame_index: 0,
+        })
+    }
+
+    /// 设置视频帧率, [`VehicleCounter::process_frame`] 用来换算时间, 默认25帧
+    pub fn set_fps(&mut self, fps: f64) {
+        self.fps = fps;
+    }
+
+    /// 当前使用的配置
+    pub fn config(&self) -> &PipelineConfig {
+        &self.config
+    }
+
+    /// 到目前为止的车辆总数
+    pub fn car_num(&self) -> usize {
+        self.counts.total()
+    }
+
+    /// 到目前为止分方向的车辆总数
+    pub fn counts(&self) -> Counts {
+        self.counts
+    }
+
+    /// 到目前为止分车型, 分方向的车辆总数
+    pub fn classes(&self) -> ClassCounts {
+        self.classes
+    }
+
+    /// 所有检测线
+    pub fn lines(&self) -> &[CountLine] {
+        &self.lines
+    }
+
+    /// 所有计数区域
+    pub fn zones(&self) -> &[CountZone] {
+        &self.zones
+    }
+
+    /// 所有车道
+    pub fn lanes(&self) -> &[Lane] {
+        &self.lanes
+    }
+
+    /// 当前的跟踪器
+    pub fn tracker(&self) -> &Tracker {
+        &self.tracker
+    }
+
+    /// 处理一帧(BGR), 不会修改原图, 时间按帧数和帧率推算
+    pub fn process_frame(&mut self, frame: &Mat) -> opencv::Result<FrameResult> {
+        let time = self.frame_index as f64 / self.fps;
+        self.process_frame_at(frame, time)
+    }
+
+    /// 处理 `time` 秒时的一帧(BGR), 跳帧处理时用视频里的真实时间, 跟踪和测速都按它计算
+    pub fn process_frame_at(&mut self, frame: &Mat, time: f64) -> opencv::Result<FrameResult> {
+        let DetectorOutput {
+            detections,
+            rejected,
+        } = self.detector.detect(frame)?;
+
+        self.frame_index += 1;
+
+        // 跨帧关联, 每辆车得到一个固定编号
+        let ids = self.tracker.update(&detections, time);
+
+        let mut tracks = Vec::with_capacity(ids.len());
+        let mut counted = Vec::new();
+        let mut speeding = Vec::new();
+
+        for track in self.tracker.tracks_mut() {
+            if !ids.contains(&track.id) {
+                continue;
+            }
+
+            track.lane = assign_lane(&self.lanes, &track.trail);
+
+            let mut crossed = None;
+            for line in &mut self.lines {
+                if let Some(event) = line.update(track) {
+                    crossed = crossed.or(event.direction);
+                    counted.push(event);
+                }
+            }
+
+            for zone in &mut self.zones {
+                if let Some(event) = zone.update(track) {
+                    counted.push(event);
+                }
+            }
+
+            // 总数里每辆车只算一次, 方向以第一次过的线为准
+            if let (false, Some(direction)) = (track.counted, crossed) {
+                track.counted = true;
+                self.counts.add(direction);
+                self.classes.add(track.class(), direction);
+
+                if let Some(lane) = track.lane {
+                    counted.push(self.lanes[lane].count(track, direction));
+                }
+            }
+
+            if let Some(speed) = &mut self.speed {
+                track.speed = speed.update(track.id, track.ground_point(), time)?;
+                if let Some(event) = track.speed.and_then(|kmh| speed.check(track.id, kmh)) {
+                    speeding.push(event);
+                }
+            }
+
+            tracks.push(track.clone());
+        }
+
+        let incidents = self.incidents.update(&tracks, &counted, time)?;
+
+        let alive: HashSet<u64> = self.tracker.tracks().iter().map(|t| t.id).collect();
+        for zone in &mut self.zones {
+            zone.retain(&alive);
+        }
+        if let Some(speed) = &mut self.speed {
+            speed.retain(&alive);
+        }
+        self.incidents.retain(&alive);
+
+        Ok(FrameResult {
+            detections,
+            rejected,
+            tracks,
+            counted,
+            speeding,
+            incidents,
+            queue: self.incidents.queue_length(),
+            counts: self.counts,
+            classes: self.classes,
+            lines: self
+                .lines
+                .iter()
+                .map(|line| LineCount {
+                    name: line.name.clone(),
+                    counts: line.counts(),
+                })
+                .collect(),
+            zones: self
+                .zones
+                .iter()
+                .map(|zone| ZoneCount {
+                    name: zone.name.clone(),
+                    entered: zone.entered(),
+                    inside: zone.inside(),
+                })
+                .collect(),
+            lanes: self
+                .lanes
+                .iter()
+                .map(|lane| LineCount {
+                    name: lane.name.clone(),
+                    counts: lane.counts(),
+                })
+                .collect(),
+        })
+    }
+
+    /// 把检测线, 车辆外框和计数画到帧上
+    pub fn draw(&self, frame: &mut Mat, result: &FrameResult) -> opencv::Result<()> {
+        // 感兴趣区域的边界
+        draw_roi(frame, &self.config)?;
+
+        // 画检测线和区域, 旁边标上各自的数量
+        for (line, count) in self.lines.iter().zip(&result.lines) {
+            let text = format!(
+                "{} down: {} up: {}",
+                count.name, count.counts.down, count.counts.up
+            );
+            draw_shape(frame, &line.points, false, &text, (0, 0, 255).into())?;
+        }
+
+        for (zone, count) in self.zones.iter().zip(&result.zones) {
+            let text = format!("{}: {} ({})", count.name, count.entered, count.inside);
+            draw_shape(frame, &zone.polygon, true, &text, (255, 0, 0).into())?;
+        }
+
+        // 车道, 格式是 下行/上行
+        for (lane, count) in self.lanes.iter().zip(&result.lanes) {
+            let text = format!("{} {}/{}", count.name, count.counts.down, count.counts.up);
+            draw_shape(frame, &lane.polygon, true, &text, (255, 255, 0).into())?;
+        }
+
+        for track in &result.tracks {
+            rectangle_def(frame, track.rect, Scalar::from((0, 0, 255)))?;
+            let mut label = format!("{} {}", track.id, track.class().as_str());
+            if let Some(lane) = track.lane {
+                label.push_str(&format!(" {}", self.lanes[lane].name));
+            }
+            if let Some(speed) = track.speed {
+                label.push_str(&format!(" {:.0}km/h", speed));
+            }
+            put_text_def(
+                frame,
+                label.as_str(),
+                Point::new(track.rect.x, track.rect.y - 5),
+                FONT_HERSHEY_SIMPLEX,
+                0.6f64,
+                (0, 255, 0).into(),
+            )?;
+        }
+
+        let text = format!(
+            "{}  down: {}  up: {}",
+            result.counts.total(),
+            result.counts.down,
+            result.counts.up
+        );
+        put_text_def(
+            frame,
+            text.as_str(),
+            Point::new(500, 60),
+            FONT_HERSHEY_SIMPLEX,
+            1f64,
+            (0, 0, 255).into(),
+        )?;
+
+        // 分车型的数量, 格式是 下行/上行
+        let text = VehicleClass::ALL
+            .iter()
+            .map(|class| {
+                let counts = result.classes.get(*class);
+                format!("{} {}/{}", class.as_str(), counts.down, counts.up)
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        put_text_def(
+            frame,
+            text.as_str(),
+            Point::new(500, 90),
+            FONT_HERSHEY_SIMPLEX,
+            0.7f64,
+            (0, 0, 255).into(),
+        )?;
+
+        if let Some(length) = result.queue {
+            let text = format!("queue: {:.1}{}", length, self.incidents.length_unit());
+            put_text_def(
+                frame,
+                text.as_str(),
+                Point::new(500, 120),
+                FONT_HERSHEY_SIMPLEX,
+                0.7f64,
+                (0, 0, 255).into(),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 画一条折线或者一个多边形, 在第一个顶点旁边标上 `label`
+fn draw_shape(
+    frame: &mut Mat,
+    points: &[Point],
+    closed: bool,
+    label: &str,
+    color: Scalar,
+) -> opencv::Result<()> {
+    let Some(&first) = points.first() else {
+        return Ok(());
+    };
+
+    polylines_def(
+        frame,
+        &VectorOfPoint::from_iter(points.iter().copied()),
+        closed,
+        color,
+    )?;
+    put_text_def(frame, label, first, FONT_HERSHEY_SIMPLEX, 0.6f64, color)
+}
+
+fn draw_roi(frame: &mut Mat, config: &PipelineConfig) -> opencv::Result<()> {
+    if let Some(roi) = &config.roi {
+        for polygon in &roi.polygons {
+            let points = VectorOfPoint::from_iter(to_points(polygon));
+            polylines_def(frame, &points, true, (0, 255, 255).into())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 把配置里的检测线, 计数区域, 车道, 感兴趣区域和测速标定点画到帧上, 标定之后检查用
+///
+/// 检测线红色, 计数区域蓝色, 车道青色, 感兴趣区域黄色, 标定点绿色并标上路面坐标
+pub fn draw_geometry(frame: &mut Mat, config: &PipelineConfig) -> opencv::Result<()> {
+    draw_roi(frame, config)?;
+
+    for line in &config.lines {
+        draw_shape(
+            frame,
+            &to_points(&line.points),
+            false,
+            &line.name,
+            (0, 0, 255).into(),
+        )?;
+    }
+
+    for zone in &config.zones {
+        draw_shape(
+            frame,
+            &to_points(&zone.polygon),
+            true,
+            &zone.name,
+            (255, 0, 0).into(),
+        )?;
+    }
+
+    for lane in &config.lanes {
+        draw_shape(
+            frame,
+            &to_points(&lane.polygon),
+            true,
+            &lane.name,
+            (255, 255, 0).into(),
+        )?;
+    }
+
+    if let Some(calibration) = &config.calibration {
+        let points: Vec<Point> = calibration
+            .image
+            .iter()
+            .map(|[x, y]| Point::new(x.round() as i32, y.round() as i32))
+            .collect();
+        polylines_def(
+            frame,
+            &VectorOfPoint::from_iter(points.iter().copied()),
+            true,
+            (0, 255, 0).into(),
+        )?;
+        for (point, [x, y]) in points.iter().zip(&calibration.world) {
+            circle_def(frame, *point, 5, (0, 255, 0).into())?;
+            put_text_def(
+                frame,
+                &format!("({:.2}, {:.2})m", x, y),
+                Point::new(point.x + 8, point.y - 8),
+                FONT_HERSHEY_SIMPLEX,
+                0.5f64,
+                (0, 255, 0).into(),
+            )?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! 感兴趣区域: 人行道, 树, 画面上的时间戳这些地方不找车
+use opencv::core::{bitwise_or, no_array, Point, Rect, Scalar, Size, StsError, CV_8UC1};
+use opencv::imgcodecs::{imread, IMREAD_GRAYSCALE};
+use opencv::imgproc::{
+    bounding_rect, fill_poly_def, resize, threshold, INTER_NEAREST, THRESH_BINARY,
+};
+use opencv::prelude::*;
+use opencv::types::{VectorOfPoint, VectorOfVectorOfPoint};
+
+use crate::config::{to_points, RoiConfig};
+
+/// 区域掩码, 第一次用的时候按画面大小生成
+pub struct Roi {
+    polygons: Vec<Vec<Point>>,
+    /// 配置里的掩码图片, 灰度
+    image: Option<Mat>,
+    crop: bool,
+    /// 掩码是按多大的画面生成的
+    size: Size,
+    /// 整个画面大小的掩码, 区域里是255
+    mask: Mat,
+    /// 区域的外接矩形
+    rect: Rect,
+    /// 外接矩形里的那部分掩码
+    cropped: Mat,
+}
+
+impl Roi {
+    pub fn new(config: &RoiConfig) -> opencv::Result<Self> {
+        let image = match &config.mask {
+            Some(path) => {
+                let image = imread(path, IMREAD_GRAYSCALE)?;
+                if image.empty() {
+                    return Err(opencv::Error::new(
+                        StsError,
+                        format!("cannot read roi mask {}", path),
+                    ));
+                }
+                Some(image)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            polygons: config.polygons.iter().map(|p| to_points(p)).collect(),
+            image,
+            crop: config.crop,
+            size: Size::default(),
+            mask: Mat::default(),
+            rect: Rect::default(),
+            cropped: Mat::default(),
+        })
+    }
+
+    /// 画面大小变了才重新生成掩码
+    pub fn update(&mut self, size: Size) -> opencv::Result<()> {
+        if size == self.size {
+            return Ok(());
+        }
+
+        let mut mask = Mat::zeros(size.height, size.width, CV_8UC1)?.to_mat()?;
+        if !self.polygons.is_empty() {
+            let polygons = VectorOfVectorOfPoint::from_iter(
+                self.polygons
+                    .iter()
+                    .map(|polygon| VectorOfPoint::from_iter(polygon.iter().copied())),
+            );
+            fill_poly_def(&mut mask, &polygons, Scalar::all(255f64))?;
+        }
+
+        if let Some(image) = &self.image {
+            let mut resized = Mat::default();
+            resize(image, &mut resized, size, 0f64, 0f64, INTER_NEAREST)?;
+
+            let mut binary = Mat::default();
+            threshold(&resized, &mut binary, 0f64, 255f64, THRESH_BINARY)?;
+
+            let mut merged = Mat::default();
+            bitwise_or(&mask, &binary, &mut merged, &no_array())?;
+            mask = merged;
+        }
+
+        let rect = bounding_rect(&mask)?;
+        if rect.width <= 0 || rect.height <= 0 {
+            return Err(opencv::Error::new(
+                StsError,
+                format!(
+                    "roi does not cover any part of the {}x{} frame",
+                    size.width, size.height
+                ),
+            ));
+        }
+
+        self.cropped = Mat::roi(&mask, rect)?.try_clone()?;
+        self.mask = mask;
+        self.rect = rect;
+        self.size = size;
+
+        Ok(())
+    }
+
+    /// 开了 `crop` 时只处理这个矩形
+    pub fn crop(&self) -> Option<Rect> {
+        self.crop.then_some(self.rect)
+    }
+
+    /// 掩码, 开了 `crop` 时是外接矩形里的那部分
+    pub fn mask(&self) -> &Mat {
+        if self.crop {
+            &self.cropped
+        } else {
+            &self.mask
+        }
+    }
+
+    /// 区域的多边形, 画图用
+    pub fn polygons(&self) -> &[Vec<Point>] {
+        &self.polygons
+    }
+}
@@ -0,0 +1,109 @@
+//! 去阴影: 车辆的影子会被背景模型当成前景, 相邻的车连成一块, 外框也偏大
+use opencv::core::{bitwise_not_def, Vec3b, CV_32FC3, CV_8UC3};
+use opencv::imgproc::{
+    accumulate_weighted, cvt_color_def, threshold, COLOR_BGR2HSV, THRESH_BINARY,
+};
+use opencv::prelude::*;
+
+use crate::config::{BackgroundConfig, ShadowConfig, ShadowMethod};
+
+/// MOG2 / KNN 在掩码里标记阴影用的值
+const SHADOW_VALUE: f64 = 127f64;
+
+/// 放在去背景和腐蚀之间, 把掩码里的阴影像素清零
+pub struct ShadowRemover {
+    config: ShadowConfig,
+    /// 直接用背景模型标出来的阴影
+    labels: bool,
+    /// 彩色背景的滑动平均(CV_32FC3), 只有按 HSV 检测时才用
+    background: Mat,
+}
+
+impl ShadowRemover {
+    /// `auto` 时, 背景模型能标阴影就用它的标记, 否则按 HSV 检测
+    pub fn new(config: &ShadowConfig, background: &BackgroundConfig) -> Self {
+        let labels = match config.method {
+            ShadowMethod::Auto => background.labels_shadows(),
+            ShadowMethod::Labels => true,
+            ShadowMethod::Hsv => false,
+        };
+
+        Self {
+            config: config.clone(),
+            labels,
+            background: Mat::default(),
+        }
+    }
+
+    /// `frame` 是原始的 BGR 帧, `mask` 是背景模型的输出, 原地修改
+    pub fn apply(&mut self, frame: &Mat, mask: &mut Mat) -> opencv::Result<()> {
+        if self.labels {
+            // 阴影是127, 前景是255, 只留下前景
+            let mut foreground = Mat::default();
+            threshold(mask, &mut foreground, SHADOW_VALUE, 255f64, THRESH_BINARY)?;
+            *mask = foreground;
+            return Ok(());
+        }
+
+        self.remove_hsv(frame, mask)
+    }
+
+    /// 阴影处亮度比背景低一些, 色调和饱和度基本不变
+    fn remove_hsv(&mut self, frame: &Mat, mask: &mut Mat) -> opencv::Result<()> {
+        // 第一帧只用来初始化背景
+        if self.background.empty() {
+            frame.convert_to_def(&mut self.background, CV_32FC3)?;
+            return Ok(());
+        }
+
+        // 只用背景像素更新彩色背景, 车和它的影子都不会混进背景里, 所以要在清掉阴影之前取反
+        let mut still = Mat::default();
+        bitwise_not_def(mask, &mut still)?;
+
+        let mut background = Mat::default();
+        self.background.convert_to_def(&mut background, CV_8UC3)?;
+
+        let mut frame_hsv = Mat::default();
+        cvt_color_def(frame, &mut frame_hsv, COLOR_BGR2HSV)?;
+        let mut background_hsv = Mat::default();
+        cvt_color_def(&background, &mut background_hsv, COLOR_BGR2HSV)?;
+
+        let frame_pixels = frame_hsv.data_typed::<Vec3b>()?;
+        let background_pixels = background_hsv.data_typed::<Vec3b>()?;
+        for ((value, pixel), background_pixel) in mask
+            .data_typed_mut::<u8>()?
+            .iter_mut()
+            .zip(frame_pixels)
+            .zip(background_pixels)
+        {
+            if *value != 0 && self.is_shadow(pixel, background_pixel) {
+                *value = 0;
+            }
+        }
+
+        accumulate_weighted(
+            frame,
+            &mut self.background,
+            self.config.learning_rate,
+            &still,
+        )?;
+
+        Ok(())
+    }
+
+    fn is_shadow(&self, pixel: &Vec3b, background: &Vec3b) -> bool {
+        if background[2] == 0 {
+            return false;
+        }
+
+        let ratio = pixel[2] as f64 / background[2] as f64;
+        // opencv 的色调范围是 0..180, 首尾相接
+        let hue = (pixel[0] as i32 - background[0] as i32).abs();
+        let hue = hue.min(180 - hue);
+        let saturation = (pixel[1] as i32 - background[1] as i32).abs();
+
+        (self.config.threshold..=self.config.max_ratio).contains(&ratio)
+            && hue <= self.config.max_hue_diff
+            && saturation <= self.config.max_saturation_diff
+    }
+}
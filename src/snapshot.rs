@@ -0,0 +1,88 @@
+//! 事件快照: 把车辆附近的那块画面存成图片, 也可以存整帧并画上车辆外框
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use opencv::core::{Rect, Scalar, StsError};
+use opencv::imgcodecs::imwrite_def;
+use opencv::imgproc::rectangle_def;
+use opencv::prelude::*;
+
+/// 快照的图片格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImageFormat {
+    Jpg,
+    Png,
+}
+
+impl ImageFormat {
+    /// 文件扩展名, imgcodecs 按它选择编码
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpg => "jpg",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+/// 快照存到同一个目录, 文件名由调用方给, 扩展名按格式加上
+pub struct SnapshotWriter {
+    dir: PathBuf,
+    format: ImageFormat,
+    /// 外接矩形四周多留的像素
+    margin: i32,
+}
+
+impl SnapshotWriter {
+    /// 目录不存在时会创建
+    pub fn new(dir: impl AsRef<Path>, format: ImageFormat) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            dir,
+            format,
+            margin: 10,
+        })
+    }
+
+    /// 截下 `rect` 附近的画面, 先裁到画面范围内, 完全在画面外时才返回错误; 返回文件路径
+    pub fn save(&self, frame: &Mat, rect: Rect, name: &str) -> opencv::Result<PathBuf> {
+        let left = (rect.x - self.margin).max(0);
+        let top = (rect.y - self.margin).max(0);
+        let right = (rect.x + rect.width + self.margin).min(frame.cols());
+        let bottom = (rect.y + rect.height + self.margin).min(frame.rows());
+        if right <= left || bottom <= top {
+            return Err(opencv::Error::new(
+                StsError,
+                format!("snapshot {} is outside the frame", name),
+            ));
+        }
+
+        let crop =
+            Mat::roi(frame, Rect::new(left, top, right - left, bottom - top))?.try_clone()?;
+        self.write(&crop, name)
+    }
+
+    /// 存整帧, 在副本上画出 `rect`, 不会修改原图, 返回文件路径
+    pub fn save_frame(&self, frame: &Mat, rect: Rect, name: &str) -> opencv::Result<PathBuf> {
+        let mut image = frame.try_clone()?;
+        rectangle_def(&mut image, rect, Scalar::from((0, 255, 0)))?;
+        self.write(&image, name)
+    }
+
+    fn write(&self, image: &Mat, name: &str) -> opencv::Result<PathBuf> {
+        let path = self
+            .dir
+            .join(format!("{}.{}", name, self.format.extension()));
+        if !imwrite_def(&path.to_string_lossy(), image)? {
+            return Err(opencv::Error::new(
+                StsError,
+                format!("cannot write snapshot {}", path.display()),
+            ));
+        }
+
+        Ok(path)
+    }
+}
@@ -0,0 +1,534 @@
+//! 输入源: 视频文件, 图片目录或者通配符, 摄像头编号, 网络视频流
+use std::cmp::Ordering;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use opencv::core::{Size, StsError};
+use opencv::imgcodecs::{imread, IMREAD_COLOR};
+use opencv::prelude::*;
+use opencv::videoio::{
+    VideoCapture, CAP_ANY, CAP_PROP_FPS, CAP_PROP_FRAME_HEIGHT, CAP_PROP_FRAME_WIDTH,
+    CAP_PROP_POS_MSEC, CAP_V4L2,
+};
+
+use crate::output::IMAGE_EXTENSIONS;
+
+/// 读不到帧率时按25帧算
+const DEFAULT_FPS: f64 = 25f64;
+
+/// 读到的一帧
+pub struct Frame {
+    /// 帧号, 从0开始, 跳过的帧也算
+    pub index: u64,
+    /// 在视频里的时间, 秒
+    pub time: f64,
+    pub image: Mat,
+}
+
+/// 输入源
+pub trait FrameSource {
+    /// 读下一帧, 读完了返回 `None`
+    fn read(&mut self) -> opencv::Result<Option<Frame>>;
+
+    /// 跳过一帧, 尽量不解码, 读完了返回 `false`
+    fn skip(&mut self) -> opencv::Result<bool>;
+
+    /// 帧率
+    fn fps(&self) -> f64;
+
+    /// 画面大小
+    fn size(&self) -> Size;
+}
+
+/// 不是正数的帧率(包括 NaN 和无穷大)当作没有
+fn valid_fps(fps: f64) -> Option<f64> {
+    (fps.is_finite() && fps > 0f64).then_some(fps)
+}
+
+fn error(message: String) -> opencv::Error {
+    opencv::Error::new(StsError, message)
+}
+
+/// 按输入的样子选择输入源:
+///
+/// - 纯数字是摄像头编号(Linux 上走 V4L2)
+/// - 带 `://` 的是网络视频流, 比如 `rtsp://...`
+/// - 目录是图片序列, 按文件名顺序读取
+/// - 文件名里有 `*` 或 `?` 的是图片通配符, 比如 `frames/*.png`
+/// - 其他都当作视频文件
+///
+/// `fps` 用来覆盖输入源自己的帧率, 不是正数时不用; 图片序列没有帧率, 默认按25帧
+pub fn open_source(input: &str, fps: Option<f64>) -> opencv::Result<Box<dyn FrameSource>> {
+    if let Ok(index) = input.parse::<i32>() {
+        let api = if cfg!(target_os = "linux") {
+            CAP_V4L2
+        } else {
+            CAP_ANY
+        };
+        let capture = VideoCapture::new(index, api)?;
+        return Ok(Box::new(VideoSource::new(capture, input, true, fps)?));
+    }
+
+    if input.contains("://") {
+        let capture = VideoCapture::from_file_def(input)?;
+        return Ok(Box::new(VideoSource::new(capture, input, true, fps)?));
+    }
+
+    let path = Path::new(input);
+    if path.is_dir() {
+        return Ok(Box::new(ImageSequence::from_dir(path, fps)?));
+    }
+
+    if input.contains(['*', '?']) {
+        return Ok(Box::new(ImageSequence::from_glob(input, fps)?));
+    }
+
+    if !path.is_file() {
+        return Err(error(format!("input {} does not exist", input)));
+    }
+    let capture = VideoCapture::from_file_def(input)?;
+    Ok(Box::new(VideoSource::new(capture, input, false, fps)?))
+}
+
+/// `VideoCapture` 能打开的输入: 视频文件, 摄像头, 网络视频流
+pub struct VideoSource {
+    capture: VideoCapture,
+    /// 摄像头和视频流按真实经过的时间算, 视频文件按视频里的位置算
+    live: bool,
+    started: Instant,
+    fps: f64,
+    size: Size,
+    index: u64,
+}
+
+impl VideoSource {
+    pub fn new(
+        capture: VideoCapture,
+        name: &str,
+        live: bool,
+        fps: Option<f64>,
+    ) -> opencv::Result<Self> {
+        if !capture.is_opened()? {
+            return Err(error(format!("cannot open input {}", name)));
+        }
+
+        let fps = match fps.and_then(valid_fps) {
+            Some(fps) => fps,
+            None => valid_fps(capture.get(CAP_PROP_FPS)?).unwrap_or(DEFAULT_FPS),
+        };
+        let size = Size::new(
+            capture.get(CAP_PROP_FRAME_WIDTH)? as i32,
+            capture.get(CAP_PROP_FRAME_HEIGHT)? as i32,
+        );
+
+        Ok(Self {
+            capture,
+            live,
+            started: Instant::now(),
+            fps,
+            size,
+            index: 0,
+        })
+    }
+
+    /// 当前帧的时间, 读不到位置时按帧号和帧率推算
+    fn time(&self) -> opencv::Result<f64> {
+        if self.live {
+            return Ok(self.started.elapsed().as_secs_f64());
+        }
+
+        let msec = self.capture.get(CAP_PROP_POS_MSEC)?;
+        if msec > 0f64 {
+            Ok(msec / 1000f64)
+        } else {
+            Ok(self.index as f64 / self.fps)
+        }
+    }
+}
+
+impl FrameSource for VideoSource {
+    fn read(&mut self) -> opencv::Result<Option<Frame>> {
+        let mut image = Mat::default();
+        if !self.capture.read(&mut image)? || image.empty() {
+            return Ok(None);
+        }
+
+        let frame = Frame {
+            index: self.index,
+            time: self.time()?,
+            image,
+        };
+        self.index += 1;
+
+        Ok(Some(frame))
+    }
+
+    fn skip(&mut self) -> opencv::Result<bool> {
+        if !self.capture.grab()? {
+            return Ok(false);
+        }
+        self.index += 1;
+
+        Ok(true)
+    }
+
+    fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl Drop for VideoSource {
+    fn drop(&mut self) {
+        let _ = self.capture.release();
+    }
+}
+
+/// 图片序列, 按文件名顺序读取, 时间按帧号和帧率推算
+pub struct ImageSequence {
+    paths: Vec<PathBuf>,
+    fps: f64,
+    size: Size,
+    index: u64,
+}
+
+impl ImageSequence {
+    pub fn new(paths: Vec<PathBuf>, fps: Option<f64>) -> opencv::Result<Self> {
+        let first = paths
+            .first()
+            .ok_or_else(|| error("image sequence is empty".to_string()))?;
+        let size = read_image(first)?.size()?;
+
+        Ok(Self {
+            paths,
+            fps: fps.and_then(valid_fps).unwrap_or(DEFAULT_FPS),
+            size,
+            index: 0,
+        })
+    }
+
+    /// 目录里所有的图片
+    pub fn from_dir(dir: &Path, fps: Option<f64>) -> opencv::Result<Self> {
+        Self::new(list_images(dir, |_| true)?, fps)
+    }
+
+    /// 文件名匹配通配符的图片, 只支持文件名里的 `*` 和 `?`
+    pub fn from_glob(pattern: &str, fps: Option<f64>) -> opencv::Result<Self> {
+        let pattern = Path::new(pattern);
+        let dir = match pattern.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let name = pattern
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| error(format!("invalid pattern {}", pattern.display())))?;
+        let name: Vec<char> = name.chars().collect();
+
+        Self::new(
+            list_images(dir, |file| {
+                wildcard_match(&name, &file.chars().collect::<Vec<_>>())
+            })?,
+            fps,
+        )
+    }
+}
+
+impl FrameSource for ImageSequence {
+    fn read(&mut self) -> opencv::Result<Option<Frame>> {
+        let Some(path) = self.paths.get(self.index as usize) else {
+            return Ok(None);
+        };
+
+        let frame = Frame {
+            index: self.index,
+            time: self.index as f64 / self.fps,
+            image: read_image(path)?,
+        };
+        self.index += 1;
+
+        Ok(Some(frame))
+    }
+
+    fn skip(&mut self) -> opencv::Result<bool> {
+        if self.index as usize >= self.paths.len() {
+            return Ok(false);
+        }
+        self.index += 1;
+
+        Ok(true)
+    }
+
+    fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+fn read_image(path: &Path) -> opencv::Result<Mat> {
+    let image = imread(&path.to_string_lossy(), IMREAD_COLOR)?;
+    if image.empty() {
+        return Err(error(format!("cannot read image {}", path.display())));
+    }
+
+    Ok(image)
+}
+
+/// 比较文件名, 连续的数字按数值比较, 这样 `frame_2` 排在 `frame_10` 前面
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(x), Some(y)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+
+        if x.is_ascii_digit() && y.is_ascii_digit() {
+            let a_end = a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len());
+            let b_end = b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len());
+            // 去掉前导零之后先比位数, 再逐位比较, 数字再长也不会溢出
+            let a_digits = a[..a_end].trim_start_matches('0');
+            let b_digits = b[..b_end].trim_start_matches('0');
+            let order = a_digits
+                .len()
+                .cmp(&b_digits.len())
+                .then_with(|| a_digits.cmp(b_digits));
+            if order != Ordering::Equal {
+                return order;
+            }
+            a = &a[a_end..];
+            b = &b[b_end..];
+        } else {
+            if x != y {
+                return x.cmp(&y);
+            }
+            a = &a[x.len_utf8()..];
+            b = &b[y.len_utf8()..];
+        }
+    }
+}
+
+/// 目录里扩展名是图片并且文件名满足 `filter` 的文件, 按文件名排序, 见 [`natural_cmp`]
+fn list_images(dir: &Path, filter: impl Fn(&str) -> bool) -> opencv::Result<Vec<PathBuf>> {
+    let entries = fs::read_dir(dir)
+        .map_err(|err| error(format!("cannot read directory {}: {}", dir.display(), err)))?;
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            let is_image = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+            let name = path.file_name().and_then(|name| name.to_str());
+            is_image && name.is_some_and(&filter) && path.is_file()
+        })
+        .collect();
+    // 同样的数值可能有不同的前导零, 最后按原始文件名区分
+    paths.sort_by(|a, b| {
+        let (a_name, b_name) = (a.file_name().unwrap(), b.file_name().unwrap());
+        natural_cmp(&a_name.to_string_lossy(), &b_name.to_string_lossy())
+            .then_with(|| a_name.cmp(b_name))
+    });
+
+    if paths.is_empty() {
+        return Err(error(format!("no images found in {}", dir.display())));
+    }
+
+    Ok(paths)
+}
+
+/// `*` 匹配任意多个字符, `?` 匹配一个字符
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| wildcard_match(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && wildcard_match(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && wildcard_match(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencv::core::{Scalar, CV_8UC3};
+    use opencv::imgcodecs::imwrite_def;
+
+    /// 每个测试自己的临时目录, 进程号区分同时跑的测试
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("cv_demo_source_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// 写一张 6x4 的黑色小图
+    fn write_image(path: &Path) {
+        let image = Mat::new_rows_cols_with_default(4, 6, CV_8UC3, Scalar::all(0f64)).unwrap();
+        assert!(imwrite_def(&path.to_string_lossy(), &image).unwrap());
+    }
+
+    fn read_all(source: &mut dyn FrameSource) -> Vec<Frame> {
+        let mut frames = Vec::new();
+        while let Some(frame) = source.read().unwrap() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let name: Vec<char> = name.chars().collect();
+        wildcard_match(&pattern, &name)
+    }
+
+    fn names(paths: &[PathBuf]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn wildcard_star() {
+        assert!(matches("*.png", "a.png"));
+        assert!(matches("*.png", ".png"));
+        assert!(!matches("*.png", "a.jpg"));
+        assert!(matches("a*b*c", "axxbyyc"));
+        assert!(!matches("a*b*c", "axxbyy"));
+    }
+
+    #[test]
+    fn wildcard_question_mark() {
+        assert!(matches("frame_??.png", "frame_01.png"));
+        assert!(!matches("frame_??.png", "frame_1.png"));
+        assert!(!matches("frame_??.png", "frame_001.png"));
+    }
+
+    #[test]
+    fn wildcard_empty_pattern() {
+        assert!(matches("", ""));
+        assert!(!matches("", "a.png"));
+    }
+
+    #[test]
+    fn wildcard_trailing_star() {
+        assert!(matches("frame*", "frame"));
+        assert!(matches("frame*", "frame_001.png"));
+        assert!(matches("*", ""));
+        assert!(!matches("frame*", "fram"));
+    }
+
+    #[test]
+    fn natural_order() {
+        let mut names = vec![
+            "frame_10.png",
+            "frame_2.png",
+            "frame_1.png",
+            "frame_01.png",
+            "frame.png",
+            "frame_1b.png",
+        ];
+        names.sort_by(|a, b| natural_cmp(a, b).then_with(|| a.cmp(b)));
+        assert_eq!(
+            names,
+            [
+                "frame.png",
+                "frame_01.png",
+                "frame_1.png",
+                "frame_1b.png",
+                "frame_2.png",
+                "frame_10.png"
+            ]
+        );
+    }
+
+    #[test]
+    fn list_images_sorted_and_filtered() {
+        let dir = temp_dir("list");
+        for name in [
+            "b.png",
+            "a.jpg",
+            "10.png",
+            "2.png",
+            "c.PNG",
+            "notes.txt",
+            "d",
+        ] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        fs::create_dir(dir.join("e.png")).ok();
+
+        // 目录 e.png 不算图片, 数字按数值排序
+        let paths = list_images(&dir, |_| true).unwrap();
+        assert_eq!(
+            names(&paths),
+            ["2.png", "10.png", "a.jpg", "b.png", "c.PNG"]
+        );
+
+        let paths = list_images(&dir, |name| name.starts_with('a')).unwrap();
+        assert_eq!(names(&paths), ["a.jpg"]);
+
+        assert!(list_images(&dir, |_| false).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_source_dispatch() {
+        let dir = temp_dir("open");
+        for name in ["f_1.png", "f_2.png", "g_1.png"] {
+            write_image(&dir.join(name));
+        }
+        fs::write(dir.join("notes.txt"), b"").unwrap();
+
+        // 目录: 所有图片
+        let mut source = open_source(&dir.to_string_lossy(), None).unwrap();
+        assert_eq!(source.size(), Size::new(6, 4));
+        assert_eq!(read_all(source.as_mut()).len(), 3);
+
+        // 通配符: 只有匹配的图片
+        let pattern = dir.join("f_?.png");
+        let mut source = open_source(&pattern.to_string_lossy(), None).unwrap();
+        assert_eq!(read_all(source.as_mut()).len(), 2);
+
+        // 不存在的文件不会交给 VideoCapture
+        let missing = dir.join("missing.mp4");
+        let err = open_source(&missing.to_string_lossy(), None).err().unwrap();
+        assert!(err.message.contains("does not exist"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn image_sequence_timestamps() {
+        let dir = temp_dir("sequence");
+        let paths: Vec<PathBuf> = (0..4).map(|i| dir.join(format!("{}.png", i))).collect();
+        for path in &paths {
+            write_image(path);
+        }
+
+        let mut sequence = ImageSequence::new(paths.clone(), Some(10f64)).unwrap();
+        assert_eq!(sequence.fps(), 10f64);
+        let first = sequence.read().unwrap().unwrap();
+        assert_eq!((first.index, first.time), (0, 0f64));
+        assert!(sequence.skip().unwrap());
+        let third = sequence.read().unwrap().unwrap();
+        assert_eq!(third.index, 2);
+        assert!((third.time - 0.2).abs() < 1e-9);
+
+        // 帧率不合法时按默认帧率
+        let sequence = ImageSequence::new(paths.clone(), Some(0f64)).unwrap();
+        assert_eq!(sequence.fps(), DEFAULT_FPS);
+        let sequence = ImageSequence::new(paths, Some(f64::NAN)).unwrap();
+        assert_eq!(sequence.fps(), DEFAULT_FPS);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,136 @@
+//! 测速: 用路面上四个已知距离的点标定透视变换, 把车辆外框底边的中点投影到路面坐标(米)
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use opencv::core::{perspective_transform, Point, Point2f, StsError, DECOMP_LU};
+use opencv::imgproc::get_perspective_transform;
+use opencv::prelude::*;
+use opencv::types::VectorOfPoint2f;
+
+use crate::config::CalibrationConfig;
+use crate::geometry::has_collinear_triple;
+
+/// 米每秒换算成公里每小时
+const MS_TO_KMH: f64 = 3.6;
+
+/// 一次超速
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpeedEvent {
+    pub track_id: u64,
+    /// 公里每小时
+    pub speed: f64,
+    /// 配置里的限速
+    pub limit: f64,
+}
+
+/// 图像坐标到路面坐标的透视变换
+pub struct GroundPlane {
+    homography: Mat,
+}
+
+impl GroundPlane {
+    /// `image` 是图上的四个点(像素), `world` 是它们在路面上的位置(米), 任意三点不能共线
+    pub fn new(image: &[[f64; 2]], world: &[[f64; 2]]) -> opencv::Result<Self> {
+        if has_collinear_triple(image) || has_collinear_triple(world) {
+            return Err(opencv::Error::new(
+                StsError,
+                "calibration points must not be repeated or have three on one line".to_string(),
+            ));
+        }
+
+        let src = to_points2f(image);
+        let dst = to_points2f(world);
+        let homography = get_perspective_transform(&src, &dst, DECOMP_LU)?;
+
+        Ok(Self { homography })
+    }
+
+    /// 把像素坐标投影到路面上, 单位是米
+    pub fn project(&self, point: Point) -> opencv::Result<Point2f> {
+        let src = VectorOfPoint2f::from_iter([Point2f::new(point.x as f32, point.y as f32)]);
+        let mut dst = VectorOfPoint2f::new();
+        perspective_transform(&src, &mut dst, &self.homography)?;
+
+        dst.get(0)
+    }
+}
+
+fn to_points2f(points: &[[f64; 2]]) -> VectorOfPoint2f {
+    points
+        .iter()
+        .map(|[x, y]| Point2f::new(*x as f32, *y as f32))
+        .collect()
+}
+
+/// 按每条轨迹最近 `window` 秒走过的路面距离估计车速
+pub struct SpeedEstimator {
+    plane: GroundPlane,
+    /// 秒
+    window: f64,
+    limit: Option<f64>,
+    /// 每条轨迹的 (时间, 路面坐标)
+    history: HashMap<u64, VecDeque<(f64, Point2f)>>,
+    /// 已经报过超速的轨迹, 每辆车只报一次
+    reported: HashSet<u64>,
+}
+
+impl SpeedEstimator {
+    pub fn new(config: &CalibrationConfig) -> opencv::Result<Self> {
+        Ok(Self {
+            plane: GroundPlane::new(&config.image, &config.world)?,
+            window: config.window,
+            limit: config.speed_limit,
+            history: HashMap::new(),
+            reported: HashSet::new(),
+        })
+    }
+
+    /// 记录轨迹在 `time` 秒时的位置, 返回当前车速(公里每小时), 数据不够时返回 `None`;
+    /// `point` 要是车辆接触路面的位置, 见 [`Track::ground_point`](crate::tracker::Track::ground_point)
+    pub fn update(
+        &mut self,
+        track_id: u64,
+        point: Point,
+        time: f64,
+    ) -> opencv::Result<Option<f64>> {
+        let position = self.plane.project(point)?;
+
+        let history = self.history.entry(track_id).or_default();
+        history.push_back((time, position));
+        while history.len() > 2 && time - history[1].0 >= self.window {
+            history.pop_front();
+        }
+
+        // 时间太短的话, 中心点抖动几个像素就会算出很大的速度
+        let (start, from) = history[0];
+        let elapsed = time - start;
+        if elapsed < self.window / 2f64 {
+            return Ok(None);
+        }
+
+        let dx = (position.x - from.x) as f64;
+        let dy = (position.y - from.y) as f64;
+        let meters = (dx * dx + dy * dy).sqrt();
+
+        Ok(Some(meters / elapsed * MS_TO_KMH))
+    }
+
+    /// 超过限速并且这辆车还没报过时返回超速事件
+    pub fn check(&mut self, track_id: u64, speed: f64) -> Option<SpeedEvent> {
+        let limit = self.limit?;
+        if speed <= limit || !self.reported.insert(track_id) {
+            return None;
+        }
+
+        Some(SpeedEvent {
+            track_id,
+            speed,
+            limit,
+        })
+    }
+
+    /// 丢掉已经消失的轨迹
+    pub fn retain(&mut self, alive: &HashSet<u64>) {
+        self.history.retain(|id, _| alive.contains(id));
+        self.reported.retain(|id| alive.contains(id));
+    }
+}
@@ -0,0 +1,353 @@
+//! 按时间段汇总的交通统计: 每条检测线 / 每个区域 / 每条车道的流量, 占有率和平均车速
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::counting::Direction;
+use crate::export::csv_field;
+use crate::pipeline::FrameResult;
+
+/// 统计的是检测线, 区域还是车道
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LocationKind {
+    Line,
+    Zone,
+    Lane,
+}
+
+impl LocationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LocationKind::Line => "line",
+            LocationKind::Zone => "zone",
+            LocationKind::Lane => "lane",
+        }
+    }
+}
+
+/// 一个时间段里一条检测线, 一个区域或者一条车道的统计
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatsRow {
+    /// 时间段长度, 分钟
+    pub interval: f64,
+    /// 时间段的开始和结束, 秒, 最后一段到视频结束为止
+    pub start: f64,
+    pub end: f64,
+    pub location: String,
+    pub kind: LocationKind,
+    /// 检测线和车道分方向的数量
+    pub down: usize,
+    pub up: usize,
+    /// 进入区域的数量
+    pub entered: usize,
+    pub total: usize,
+    /// 流量, 辆每小时
+    pub flow: f64,
+    /// 区域里有车的时间占比, 检测线没有
+    pub occupancy: Option<f64>,
+    /// 这段时间计数的车的平均车速, 公里每小时, 没有测速时没有
+    pub avg_speed: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Bucket {
+    down: usize,
+    up: usize,
+    entered: usize,
+    /// 区域里有车的秒数
+    occupied: f64,
+    speed_sum: f64,
+    speed_count: usize,
+}
+
+impl Bucket {
+    fn merge(&mut self, other: &Bucket) {
+        self.down += other.down;
+        self.up += other.up;
+        self.entered += other.entered;
+        self.occupied += other.occupied;
+        self.speed_sum += other.speed_sum;
+        self.speed_count += other.speed_count;
+    }
+}
+
+/// 按固定长度的时间段累计计数, 可以同时统计多种长度(比如1, 5, 15分钟)
+pub struct TrafficStats {
+    /// 时间段长度, 秒
+    intervals: Vec<f64>,
+    locations: Vec<(String, LocationKind)>,
+    /// (时间段长度的下标, 第几段, 位置的下标) -> 统计
+    buckets: HashMap<(usize, u64, usize), Bucket>,
+    /// 上一帧里有车的区域
+    occupied: Vec<usize>,
+    last_time: Option<f64>,
+}
+
+impl TrafficStats {
+    /// `minutes` 是时间段长度, 分钟
+    pub fn new(minutes: &[f64]) -> Self {
+        Self {
+            intervals: minutes.iter().map(|minutes| minutes * 60f64).collect(),
+            locations: Vec::new(),
+            buckets: HashMap::new(),
+            occupied: Vec::new(),
+            last_time: None,
+        }
+    }
+
+    fn location(&self, name: &str) -> Option<usize> {
+        self.locations
+            .iter()
+            .position(|(location, _)| location == name)
+    }
+
+    fn bucket(&mut self, interval: usize, time: f64, location: usize) -> &mut Bucket {
+        let index = (time / self.intervals[interval]).floor() as u64;
+        self.buckets.entry((interval, index, location)).or_default()
+    }
+
+    /// 把 `from` 到 `to` 这段有车的时间按时间段切开累加
+    fn add_occupied(&mut self, location: usize, from: f64, to: f64) {
+        for interval in 0..self.intervals.len() {
+            let length = self.intervals[interval];
+            let mut start = from;
+            while start < to {
+                let end = to.min(((start / length).floor() + 1f64) * length);
+                self.bucket(interval, start, location).occupied += end - start;
+                start = end;
+            }
+        }
+    }
+
+    /// 每处理一帧调用一次, `time` 是这一帧的时间, 秒
+    pub fn update(&mut self, time: f64, result: &FrameResult) {
+        if self.locations.is_empty() {
+            self.locations = result
+                .lines
+                .iter()
+                .map(|line| (line.name.clone(), LocationKind::Line))
+                .chain(
+                    result
+                        .zones
+                        .iter()
+                        .map(|zone| (zone.name.clone(), LocationKind::Zone)),
+                )
+                .chain(
+                    result
+                        .lanes
+                        .iter()
+                        .map(|lane| (lane.name.clone(), LocationKind::Lane)),
+                )
+                .collect();
+        }
+
+        // 上一帧到这一帧之间区域里有没有车按上一帧算
+        if let Some(last) = self.last_time.filter(|last| *last < time) {
+            for location in std::mem::take(&mut self.occupied) {
+                self.add_occupied(location, last, time);
+            }
+        }
+        self.occupied = result
+            .zones
+            .iter()
+            .filter(|zone| zone.inside > 0)
+            .filter_map(|zone| self.location(&zone.name))
+            .collect();
+        self.last_time = Some(self.last_time.map_or(time, |last| last.max(time)));
+
+        for event in &result.counted {
+            let Some(location) = self.location(&event.zone) else {
+                continue;
+            };
+            let speed = result
+                .tracks
+                .iter()
+                .find(|track| track.id == event.track_id)
+                .and_then(|track| track.speed);
+
+            for interval in 0..self.intervals.len() {
+                let bucket = self.bucket(interval, time, location);
+                match event.direction {
+                    Some(Direction::Down) => bucket.down += 1,
+                    Some(Direction::Up) => bucket.up += 1,
+                    None => bucket.entered += 1,
+                }
+                if let Some(speed) = speed {
+                    bucket.speed_sum += speed;
+                    bucket.speed_count += 1;
+                }
+            }
+        }
+    }
+
+    /// 按时间段长度, 时间, 位置排好的所有统计, 没有车的时间段也有一行
+    pub fn rows(&self) -> Vec<StatsRow> {
+        let Some(last_time) = self.last_time else {
+            return Vec::new();
+        };
+
+        let mut rows = Vec::new();
+
+        for (interval, &length) in self.intervals.iter().enumerate() {
+            // 时间段左闭右开, 最后一帧正好在分界上时不多出一个长度为0的时间段,
+            // 那一刻的计数算进最后一段
+            let count = ((last_time / length).ceil() as u64).max(1);
+
+            for index in 0..count {
+                let start = index as f64 * length;
+                let end = (start + length).min(last_time);
+                // 最后一段可能很短, 按实际长度算流量和占有率
+                let duration = if end > start { end - start } else { length };
+
+                for (location, (name, kind)) in self.locations.iter().enumerate() {
+                    let mut bucket = self
+                        .buckets
+                        .get(&(interval, index, location))
+                        .cloned()
+                        .unwrap_or_default();
+                    if index + 1 == count {
+                        if let Some(boundary) = self.buckets.get(&(interval, count, location)) {
+                            bucket.merge(boundary);
+                        }
+                    }
+                    let total = match kind {
+                        LocationKind::Line | LocationKind::Lane => bucket.down + bucket.up,
+                        LocationKind::Zone => bucket.entered,
+                    };
+
+                    rows.push(StatsRow {
+                        interval: length / 60f64,
+                        start,
+                        end: start + duration,
+                        location: name.clone(),
+                        kind: *kind,
+                        down: bucket.down,
+                        up: bucket.up,
+                        entered: bucket.entered,
+                        total,
+                        flow: total as f64 * 3600f64 / duration,
+                        occupancy: (*kind == LocationKind::Zone)
+                            .then(|| (bucket.occupied / duration).min(1f64)),
+                        avg_speed: (bucket.speed_count > 0)
+                            .then(|| bucket.speed_sum / bucket.speed_count as f64),
+                    });
+                }
+            }
+        }
+
+        rows
+    }
+
+    pub fn write_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, &self.rows())?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(
+            writer,
+            "interval,start,end,location,kind,down,up,entered,total,flow,occupancy,avg_speed"
+        )?;
+
+        for row in self.rows() {
+            writeln!(
+                writer,
+                "{},{:.3},{:.3},{},{},{},{},{},{},{:.1},{},{}",
+                row.interval,
+                row.start,
+                row.end,
+                csv_field(&row.location),
+                row.kind.as_str(),
+                row.down,
+                row.up,
+                row.entered,
+                row.total,
+                row.flow,
+                row.occupancy
+                    .map(|occupancy| format!("{:.4}", occupancy))
+                    .unwrap_or_default(),
+                row.avg_speed
+                    .map(|speed| format!("{:.1}", speed))
+                    .unwrap_or_default()
+            )?;
+        }
+
+        writer.flush()
+    }
+
+    /// 扩展名是 `.csv` 写 CSV, 其他写 JSON
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => self.write_csv(path),
+            _ => self.write_json(path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classify::VehicleClass;
+    use crate::counting::{CountEvent, Counts, LineCount};
+
+    fn frame(counted: Vec<CountEvent>) -> FrameResult {
+        FrameResult {
+            counted,
+            lines: vec![LineCount {
+                name: "line".to_string(),
+                counts: Counts::default(),
+            }],
+            ..FrameResult::default()
+        }
+    }
+
+    fn event(track_id: u64) -> CountEvent {
+        CountEvent {
+            track_id,
+            zone: "line".to_string(),
+            direction: Some(Direction::Down),
+            class: VehicleClass::Car,
+        }
+    }
+
+    #[test]
+    fn last_frame_on_boundary_adds_no_empty_bucket() {
+        let mut stats = TrafficStats::new(&[1f64]);
+        stats.update(0f64, &frame(vec![event(1)]));
+        stats.update(30f64, &frame(Vec::new()));
+        stats.update(60f64, &frame(Vec::new()));
+        // 正好在第二段结束的那一刻计数
+        stats.update(120f64, &frame(vec![event(2)]));
+
+        let rows = stats.rows();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].start, rows[0].end, rows[0].down), (0f64, 60f64, 1));
+        assert_eq!(
+            (rows[1].start, rows[1].end, rows[1].down),
+            (60f64, 120f64, 1)
+        );
+    }
+
+    #[test]
+    fn last_frame_inside_bucket_keeps_short_bucket() {
+        let mut stats = TrafficStats::new(&[1f64]);
+        stats.update(0f64, &frame(Vec::new()));
+        stats.update(90f64, &frame(vec![event(1)]));
+
+        let rows = stats.rows();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            (rows[1].start, rows[1].end, rows[1].down),
+            (60f64, 90f64, 1)
+        );
+        assert!((rows[1].flow - 120f64).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,131 @@
+//! 批量处理的汇总结果, 每个视频一行
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::classify::{ClassCounts, VehicleClass};
+use crate::counting::Counts;
+use crate::export::csv_field;
+
+/// 一个视频的处理结果
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct VideoSummary {
+    pub video: String,
+    /// 一共读了多少帧, 跳过的也算
+    pub frames: u64,
+    /// 实际处理了多少帧
+    pub processed: u64,
+    /// 最后处理的那一帧在视频里的时间, 秒; 跳帧或者视频提前结束时比视频时长短
+    pub processed_until: f64,
+    /// 处理用了多少秒
+    pub elapsed: f64,
+    /// 处理速度, 每秒处理的帧数
+    pub processing_fps: f64,
+    pub counts: Counts,
+    pub classes: ClassCounts,
+    /// 处理失败时的错误信息
+    pub error: Option<String>,
+}
+
+/// 所有视频加起来
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BatchSummary {
+    pub videos: Vec<VideoSummary>,
+    pub failed: usize,
+    /// 整个批次用了多少秒
+    pub elapsed: f64,
+    /// 整个批次每秒处理的帧数, 多个视频并行时比单个视频快
+    pub processing_fps: f64,
+    pub counts: Counts,
+    pub classes: ClassCounts,
+}
+
+impl BatchSummary {
+    /// `elapsed` 是整个批次用的时间, 秒
+    pub fn new(videos: Vec<VideoSummary>, elapsed: f64) -> Self {
+        let mut summary = Self {
+            elapsed,
+            ..Self::default()
+        };
+        let mut processed = 0;
+
+        for video in &videos {
+            if video.error.is_some() {
+                summary.failed += 1;
+            }
+
+            processed += video.processed;
+            summary.counts.merge(video.counts);
+            summary.classes.merge(video.classes);
+        }
+
+        if elapsed > 0f64 {
+            summary.processing_fps = processed as f64 / elapsed;
+        }
+
+        summary.videos = videos;
+        summary
+    }
+
+    pub fn write_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+
+    /// 每个视频一行, 最后一行 `video` 是 `TOTAL` 的是合计
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        write!(
+            writer,
+            "video,frames,processed,processed_until,elapsed,processing_fps,total,down,up"
+        )?;
+        for class in VehicleClass::ALL {
+            write!(writer, ",{0}_down,{0}_up", class.as_str())?;
+        }
+        writeln!(writer, ",error")?;
+
+        for video in &self.videos {
+            write!(
+                writer,
+                "{},{},{},{:.3},{:.3},{:.1},",
+                csv_field(&video.video),
+                video.frames,
+                video.processed,
+                video.processed_until,
+                video.elapsed,
+                video.processing_fps
+            )?;
+            write_counts(&mut writer, video.counts, video.classes)?;
+            writeln!(
+                writer,
+                ",{}",
+                csv_field(video.error.as_deref().unwrap_or_default())
+            )?;
+        }
+
+        write!(
+            writer,
+            "TOTAL,,,,{:.3},{:.1},",
+            self.elapsed, self.processing_fps
+        )?;
+        write_counts(&mut writer, self.counts, self.classes)?;
+        writeln!(writer, ",")?;
+
+        writer.flush()
+    }
+}
+
+fn write_counts(writer: &mut impl Write, counts: Counts, classes: ClassCounts) -> io::Result<()> {
+    write!(writer, "{},{},{}", counts.total(), counts.down, counts.up)?;
+    for class in VehicleClass::ALL {
+        let counts = classes.get(class);
+        write!(writer, ",{},{}", counts.down, counts.up)?;
+    }
+
+    Ok(())
+}
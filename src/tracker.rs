@@ -0,0 +1,307 @@
+use opencv::core::{Point, Rect};
+
+use crate::classify::VehicleClass;
+use crate::pipeline::Detection;
+
+/// 轨迹最多保留多少个历史中心点
+const TRAIL_LEN: usize = 64;
+
+/// 一辆被持续跟踪的车辆
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track {
+    /// 跨帧不变的编号, 从1开始
+    pub id: u64,
+    /// 最近一次匹配到的外接矩形
+    pub rect: Rect,
+    /// 历史中心点, 最后一个是当前位置
+    pub trail: Vec<Point>,
+    /// 一共匹配到多少次
+    pub hits: u32,
+    /// 连续多少帧没有匹配到
+    pub missed: u32,
+    /// 最近一次匹配到的时间, 秒
+    pub last_seen: f64,
+    /// 是否已经计过数
+    pub counted: bool,
+    /// 估计的车速, 公里每小时, 没有标定或者数据不够时是 `None`
+    pub speed: Option<f64>,
+    /// 所在车道在配置的 `lanes` 里的下标, 没有配置车道或者不在任何车道里是 `None`
+    pub lane: Option<usize>,
+    /// 每一帧的车型估计, 按 [`VehicleClass::index`] 计票
+    votes: [u32; 3],
+}
+
+impl Track {
+    fn new(id: u64, detection: &Detection, time: f64) -> Self {
+        let mut votes = [0; 3];
+        votes[detection.class.index()] = 1;
+
+        Self {
+            id,
+            rect: detection.rect,
+            trail: vec![detection.center],
+            hits: 1,
+            missed: 0,
+            last_seen: time,
+            counted: false,
+            speed: None,
+            lane: None,
+            votes,
+        }
+    }
+
+    /// 历史上得票最多的车型, 单帧的估计容易受粘连和遮挡影响
+    pub fn class(&self) -> VehicleClass {
+        let mut best = VehicleClass::Car;
+        for class in VehicleClass::ALL {
+            if self.votes[class.index()] > self.votes[best.index()] {
+                best = class;
+            }
+        }
+        best
+    }
+
+    /// 当前中心点
+    pub fn center(&self) -> Point {
+        *self.trail.last().expect("轨迹至少有一个点")
+    }
+
+    /// 外框底边的中点, 大致是车辆接触路面的位置, 投影到路面上比中心点准
+    pub fn ground_point(&self) -> Point {
+        Point::new(
+            self.rect.x + self.rect.width / 2,
+            self.rect.y + self.rect.height,
+        )
+    }
+
+    /// 上一次的中心点, 新轨迹没有
+    pub fn previous_center(&self) -> Option<Point> {
+        self.trail.iter().rev().nth(1).copied()
+    }
+
+    fn update(&mut self, detection: &Detection, time: f64) {
+        self.rect = detection.rect;
+        self.trail.push(detection.center);
+        if self.trail.len() > TRAIL_LEN {
+            self.trail.remove(0);
+        }
+        self.hits += 1;
+        self.missed = 0;
+        self.last_seen = time;
+        self.votes[detection.class.index()] += 1;
+    }
+}
+
+/// 两个矩形的交并比
+pub fn iou(a: &Rect, b: &Rect) -> f64 {
+    let x1 = a.x.max(b.x);
+    let y1 = a.y.max(b.y);
+    let x2 = (a.x + a.width).min(b.x + b.width);
+    let y2 = (a.y + a.height).min(b.y + b.height);
+
+    if x2 <= x1 || y2 <= y1 {
+        return 0f64;
+    }
+
+    let inter = ((x2 - x1) * (y2 - y1)) as f64;
+    let union = (a.width * a.height + b.width * b.height) as f64 - inter;
+
+    inter / union
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    let dx = (a.x - b.x) as f64;
+    let dy = (a.y - b.y) as f64;
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// 基于交并比 + 中心点距离的多目标跟踪
+///
+/// 先按交并比从大到小贪心匹配, 剩下的再按中心点距离从近到远匹配, 没匹配上的检测生成新轨迹,
+/// 超过 `max_age` 秒没匹配上的轨迹被丢弃。距离和时间都按真实时间算, 跳帧处理时不用改参数
+#[derive(Debug, Clone)]
+pub struct Tracker {
+    tracks: Vec<Track>,
+    next_id: u64,
+    /// 交并比至少多少才算同一辆车
+    pub iou_threshold: f64,
+    /// 交并比不够时, 中心点每秒最多移动多少像素还算同一辆车
+    pub max_speed: f64,
+    /// 轨迹最多允许丢失多少秒
+    pub max_age: f64,
+}
+
+impl Default for Tracker {
+    fn default() -> Self {
+        Self::new(0.3, 2000f64, 0.4)
+    }
+}
+
+impl Tracker {
+    pub fn new(iou_threshold: f64, max_speed: f64, max_age: f64) -> Self {
+        Self {
+            tracks: Vec::new(),
+            next_id: 1,
+            iou_threshold,
+            max_speed,
+            max_age,
+        }
+    }
+
+    /// 当前存活的轨迹(包括这一帧暂时丢失的)
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    pub fn tracks_mut(&mut self) -> &mut [Track] {
+        &mut self.tracks
+    }
+
+    /// 用 `time` 秒时这一帧的检测结果更新轨迹, 返回每个检测对应的轨迹编号
+    pub fn update(&mut self, detections: &[Detection], time: f64) -> Vec<u64> {
+        let mut track_used = vec![false; self.tracks.len()];
+        let mut assigned: Vec<Option<usize>> = vec![None; detections.len()];
+
+        // 先按交并比匹配
+        let mut pairs = Vec::new();
+        for (t, track) in self.tracks.iter().enumerate() {
+            for (d, detection) in detections.iter().enumerate() {
+                // 不重叠的框交给下面的距离匹配, 阈值是0时也一样
+                let score = iou(&track.rect, &detection.rect);
+                if score > 0f64 && score >= self.iou_threshold {
+                    pairs.push((score, t, d));
+                }
+            }
+        }
+        pairs.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Self::assign(&pairs, &mut track_used, &mut assigned);
+
+        // 剩下的按中心点距离匹配, 距离取负数, 这样同样是从大到小排序
+        let mut pairs = Vec::new();
+        for (t, track) in self.tracks.iter().enumerate() {
+            if track_used[t] {
+                continue;
+            }
+            // 离上次匹配越久, 允许移动的距离越大
+            let max_distance = self.max_speed * (time - track.last_seen).max(0f64);
+            for (d, detection) in detections.iter().enumerate() {
+                if assigned[d].is_some() {
+                    continue;
+                }
+                let dist = distance(track.center(), detection.center);
+                if dist <= max_distance {
+                    pairs.push((-dist, t, d));
+                }
+            }
+        }
+        pairs.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Self::assign(&pairs, &mut track_used, &mut assigned);
+
+        // 没匹配上的轨迹老化
+        for (t, track) in self.tracks.iter_mut().enumerate() {
+            if !track_used[t] {
+                track.missed += 1;
+            }
+        }
+
+        let mut ids = Vec::with_capacity(detections.len());
+        for (d, detection) in detections.iter().enumerate() {
+            match assigned[d] {
+                Some(t) => {
+                    self.tracks[t].update(detection, time);
+                    ids.push(self.tracks[t].id);
+                }
+                None => {
+                    let id = self.next_id;
+                    self.next_id += 1;
+                    self.tracks.push(Track::new(id, detection, time));
+                    ids.push(id);
+                }
+            }
+        }
+
+        let max_age = self.max_age;
+        self.tracks
+            .retain(|track| time - track.last_seen <= max_age);
+
+        ids
+    }
+
+    fn assign(
+        pairs: &[(f64, usize, usize)],
+        track_used: &mut [bool],
+        assigned: &mut [Option<usize>],
+    ) {
+        for &(_, t, d) in pairs {
+            if track_used[t] || assigned[d].is_some() {
+                continue;
+            }
+            track_used[t] = true;
+            assigned[d] = Some(t);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detection(x: i32, y: i32) -> Detection {
+        let rect = Rect::new(x, y, 100, 100);
+        Detection {
+            rect,
+            center: Point::new(x + 50, y + 50),
+            class: VehicleClass::Car,
+        }
+    }
+
+    #[test]
+    fn overlapping_boxes_keep_their_ids() {
+        let mut tracker = Tracker::new(0.3, 100f64, 0.4);
+        let first = tracker.update(&[detection(0, 0), detection(300, 0)], 0f64);
+        assert_eq!(first, vec![1, 2]);
+
+        // 两个框都只移动了一点, 顺序反过来也要按交并比认回原来的轨迹
+        let second = tracker.update(&[detection(310, 0), detection(10, 0)], 0.04);
+        assert_eq!(second, vec![2, 1]);
+        assert_eq!(tracker.tracks()[0].hits, 2);
+    }
+
+    #[test]
+    fn distance_fallback_is_gated_by_speed() {
+        // 400 像素每秒, 0.5 秒内最多走 200 像素
+        let mut tracker = Tracker::new(0.3, 400f64, 1f64);
+        tracker.update(&[detection(0, 0)], 0f64);
+
+        // 不重叠但是在范围内
+        assert_eq!(tracker.update(&[detection(0, 140)], 0.5), vec![1]);
+        assert_eq!(tracker.tracks()[0].trail.len(), 2);
+
+        // 不重叠并且超出范围, 只能是新车
+        assert_eq!(tracker.update(&[detection(0, 400)], 1f64), vec![2]);
+    }
+
+    #[test]
+    fn zero_threshold_does_not_pair_disjoint_boxes() {
+        let mut tracker = Tracker::new(0f64, 100f64, 1f64);
+        tracker.update(&[detection(0, 0)], 0f64);
+
+        assert_eq!(tracker.update(&[detection(800, 0)], 0.04), vec![2]);
+    }
+
+    #[test]
+    fn tracks_expire_after_max_age() {
+        let mut tracker = Tracker::new(0.3, 100f64, 0.4);
+        tracker.update(&[detection(0, 0)], 0f64);
+
+        tracker.update(&[], 0.4);
+        assert_eq!(tracker.tracks().len(), 1);
+        assert_eq!(tracker.tracks()[0].missed, 1);
+
+        tracker.update(&[], 0.5);
+        assert!(tracker.tracks().is_empty());
+
+        // 过期之后同一个位置出现的是新轨迹
+        assert_eq!(tracker.update(&[detection(0, 0)], 0.6), vec![2]);
+    }
+}
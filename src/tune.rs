@@ -0,0 +1,229 @@
+//! 在标注过的视频上搜索形态学和过滤参数
+//!
+//! 每个参数给一组候选值, 网格搜索试遍所有组合, 随机搜索每次从每组里随便挑一个。
+//! 候选值列表为空的参数保持基础配置里的值
+use std::fs;
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ConfigError, KernelShape, PipelineConfig};
+use crate::eval::EvalReport;
+
+/// 各个参数的候选值
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchSpace {
+    pub kernel_size: Vec<i32>,
+    pub kernel_shape: Vec<KernelShape>,
+    pub erode_iterations: Vec<i32>,
+    pub dilate_iterations: Vec<i32>,
+    pub close_passes: Vec<u32>,
+    pub blur_size: Vec<i32>,
+    pub blur_sigma: Vec<f64>,
+    pub min_w: Vec<i32>,
+    pub min_h: Vec<i32>,
+}
+
+impl Default for SearchSpace {
+    fn default() -> Self {
+        Self {
+            kernel_size: vec![3, 5, 7],
+            kernel_shape: vec![KernelShape::Rect, KernelShape::Ellipse],
+            erode_iterations: Vec::new(),
+            dilate_iterations: vec![1, 2, 3, 4],
+            close_passes: vec![0, 1, 2, 3],
+            blur_size: vec![3, 5],
+            blur_sigma: Vec::new(),
+            min_w: vec![30, 40, 50],
+            min_h: vec![30, 40, 50],
+        }
+    }
+}
+
+/// 一组参数, 每个字段是对应候选值列表里的下标
+type Choice = [usize; 9];
+
+impl SearchSpace {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text =
+            fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => serde_json::from_str(&text)
+                .map_err(|err| ConfigError::Parse(path.to_path_buf(), err.to_string())),
+            _ => toml::from_str(&text)
+                .map_err(|err| ConfigError::Parse(path.to_path_buf(), err.to_string())),
+        }
+    }
+
+    /// 每个参数有几个候选值, 空列表算一个(用基础配置的值)
+    fn sizes(&self) -> Choice {
+        [
+            self.kernel_size.len(),
+            self.kernel_shape.len(),
+            self.erode_iterations.len(),
+            self.dilate_iterations.len(),
+            self.close_passes.len(),
+            self.blur_size.len(),
+            self.blur_sigma.len(),
+            self.min_w.len(),
+            self.min_h.len(),
+        ]
+        .map(|size| size.max(1))
+    }
+
+    /// 网格搜索一共有多少个组合
+    pub fn grid_size(&self) -> usize {
+        self.sizes().iter().product()
+    }
+
+    fn apply(&self, base: &PipelineConfig, choice: Choice) -> PipelineConfig {
+        fn pick<T: Clone>(values: &[T], index: usize, value: &mut T) {
+            if let Some(picked) = values.get(index) {
+                *value = picked.clone();
+            }
+        }
+
+        let mut config = base.clone();
+        let morphology = &mut config.morphology;
+        pick(&self.kernel_size, choice[0], &mut morphology.kernel_size);
+        pick(&self.kernel_shape, choice[1], &mut morphology.kernel_shape);
+        pick(
+            &self.erode_iterations,
+            choice[2],
+            &mut morphology.erode_iterations,
+        );
+        pick(
+            &self.dilate_iterations,
+            choice[3],
+            &mut morphology.dilate_iterations,
+        );
+        pick(&self.close_passes, choice[4], &mut morphology.close_passes);
+        pick(&self.blur_size, choice[5], &mut config.blur.size);
+        pick(&self.blur_sigma, choice[6], &mut config.blur.sigma);
+        pick(&self.min_w, choice[7], &mut config.filter.min_w);
+        pick(&self.min_h, choice[8], &mut config.filter.min_h);
+
+        config
+    }
+
+    /// 所有组合, 不合法的组合(比如偶数的模糊核)跳过
+    pub fn grid(&self, base: &PipelineConfig) -> Vec<PipelineConfig> {
+        let sizes = self.sizes();
+        let mut candidates = Vec::new();
+        let mut choice: Choice = [0; 9];
+
+        'outer: loop {
+            let config = self.apply(base, choice);
+            if config.validate().is_ok() {
+                candidates.push(config);
+            }
+
+            // 像数字进位一样枚举下一个组合
+            for (index, size) in choice.iter_mut().zip(sizes) {
+                *index += 1;
+                if *index < size {
+                    continue 'outer;
+                }
+                *index = 0;
+            }
+            break;
+        }
+
+        candidates
+    }
+
+    /// 随机挑 `count` 个不重复的组合, 同一个 `seed` 结果一样
+    pub fn random(&self, base: &PipelineConfig, count: usize, seed: u64) -> Vec<PipelineConfig> {
+        let sizes = self.sizes();
+        let mut rng = SplitMix64(seed);
+        let mut candidates: Vec<PipelineConfig> = Vec::new();
+
+        // 组合不够或者大多不合法时不要一直试下去
+        let mut attempts = 0;
+        while candidates.len() < count && attempts < count * 100 {
+            attempts += 1;
+
+            let choice = sizes.map(|size| (rng.next() % size as u64) as usize);
+            let config = self.apply(base, choice);
+            if config.validate().is_ok() && !candidates.contains(&config) {
+                candidates.push(config);
+            }
+        }
+
+        candidates
+    }
+}
+
+/// 一个很小的伪随机数生成器, 只用来挑参数
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// 按哪个指标挑最好的配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Objective {
+    /// 检测的 F1
+    F1,
+    /// 跟踪的 MOTA
+    Mota,
+    /// 跟踪的 IDF1
+    Idf1,
+    /// 计数的相对误差, 越小越好
+    Count,
+}
+
+impl Objective {
+    /// 分数越大越好
+    pub fn score(&self, report: &EvalReport) -> f64 {
+        match self {
+            Objective::F1 => report.detection.f1,
+            Objective::Mota => report.tracking.mota,
+            Objective::Idf1 => report.tracking.idf1,
+            Objective::Count => -report.counting.relative_error,
+        }
+    }
+}
+
+/// 一组参数的简短描述, 当作评估结果的名字
+pub fn describe(config: &PipelineConfig) -> String {
+    let morphology = &config.morphology;
+    format!(
+        "kernel={} {:?} erode={} dilate={} close={} blur={}/{} min={}x{}",
+        morphology.kernel_size,
+        morphology.kernel_shape,
+        morphology.erode_iterations,
+        morphology.dilate_iterations,
+        morphology.close_passes,
+        config.blur.size,
+        config.blur.sigma,
+        config.filter.min_w,
+        config.filter.min_h
+    )
+    .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_search_space_is_wider_than_the_default() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("config/search.toml");
+        let space = SearchSpace::load(path).unwrap();
+
+        assert!(space.grid_size() > SearchSpace::default().grid_size());
+        assert!(space.sizes().iter().all(|size| *size > 1));
+    }
+}